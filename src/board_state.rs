@@ -3,7 +3,9 @@ use std::collections::{HashMap, HashSet};
 
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
-use crate::{colour::Colour, zobrist::ZobristTable, coordinate::Coordinate, fails::TurnErrors, group_state::GroupState};
+use crate::{colour::Colour, zobrist::{self, ZobristTable}, coordinate::Coordinate, fails::{BoardErrors, TurnErrors}, group_state::GroupState, rules::Rules};
+
+pub const MAX_BOARD_SIZE: usize = 25; // the largest board the zobrist keys and sgf coordinates cover
 
 #[derive(Clone, Debug)]
 pub struct BoardState {
@@ -12,74 +14,62 @@ pub struct BoardState {
     pub group_map: HashMap<usize, GroupState>,
     pub group_counter: usize,
     pub zobrist_table: ZobristTable,
+    pub prisoners: [usize; 2], // how many stones black and white have captured
+    pub rules: Rules,
+    grid: Vec<Colour>, // the colour of each point, kept in step with the groups
 }
 
 
 impl BoardState {
-    /// Creates a new board of a specified size
-    pub fn new(size: usize) -> Self {
-        BoardState {
+    /// Creates a new board of a specified size, which has to be between 1 and MAX_BOARD_SIZE
+    pub fn new(size: usize) -> Result<Self, BoardErrors> {
+        if size == 0 || size > MAX_BOARD_SIZE {
+            return Err(BoardErrors::InvalidSize(size));
+        }
+
+        Ok(BoardState {
             size,
             groups: vec![None; size * size],
             group_map: HashMap::new(),
             group_counter: 0,
             zobrist_table: ZobristTable::new(size),
-        }
+            prisoners: [0, 0],
+            rules: Rules::default(),
+            grid: vec![Colour::Empty; size * size],
+        })
     }
 
     /// Converts the list of groups into a more displayable list of colours
-    pub fn generate_grid_from_groups(groups: &Vec<Option<usize>>, map: &HashMap<usize, GroupState>, size: usize) -> Vec<Colour> {
+    pub fn generate_grid_from_groups(groups: &[Option<usize>], map: &HashMap<usize, GroupState>, size: usize) -> Vec<Colour> {
         let mut grid = vec![Colour::Empty; size * size];
 
-        for group in groups {
-            if let Some(group_id) = group {
-                let group = map.get(group_id).unwrap();
-                for position in group.get_points().iter() {
-                    grid[position.get_index()] = group.colour;
-                }
+        for group_id in groups.iter().flatten() {
+            let group = map.get(group_id).unwrap();
+            for position in group.get_points().iter() {
+                grid[position.get_index()] = group.colour;
             }
         }
 
         grid
     }
 
-    /// allows for the generate_grid_from_groups to be called from outside the struct
+    /// returns the colour of every point on the board
     pub fn get_grid(&self) -> Vec<Colour> {
-        BoardState::generate_grid_from_groups(&self.groups, &self.group_map, self.size)
+        self.grid.clone()
     }
 
-    /// takes a Vec<Option<usize>> and Vec<usize> and sets the value of the first Vec to None if that value appears in the second vector
-    pub fn remove_groups(groups: &Vec<Option<usize>>, groups_to_remove: Vec<usize>) -> Vec<Option<usize>> {
-        if groups_to_remove.is_empty() {
-            return groups.to_owned()
-        }
-
-        // Create a HashSet for quicker lookup
-        let group_ids_to_remove: HashSet<usize> = groups_to_remove.into_iter().collect();
-        
-        // Create a new vector with updated groups
-        let new_groups: Vec<Option<usize>> = groups.iter().map(|&x| {
-            if let Some(id) = x {
-                if group_ids_to_remove.contains(&id) {
-                    return None;
-                }
-                return Some(id);
-            }
-            x
-        }).collect();
-    
-        new_groups
+    /// returns the colour of a single point
+    pub fn get_colour(&self, coordinate: Coordinate) -> Colour {
+        self.grid[coordinate.get_index()]
     }
 
     /// places a stone onto the board
     /// acts as the play move for the local board instance
+    /// 
+    /// only the groups next to the stone are looked at, as every group keeps track of its own liberties
     pub fn add_stone(&self, coordinate: Coordinate, colour: Colour) -> Result<BoardState, TurnErrors> {
-        let mut new_group_map = self.group_map.clone();
-        let mut new_groups = self.groups.clone();
-        let mut new_zobrist_table = self.zobrist_table.clone(); // these all get passed to the new board
-
         // initial error checking:
-        if coordinate.get_index() >= self.groups.len() {
+        if coordinate.get_size() != self.size || !coordinate.in_bounds() {
             return Err(TurnErrors::OutofBounds);
         }
 
@@ -87,87 +77,177 @@ impl BoardState {
             return Err(TurnErrors::AlreadyPlaced);
         }
 
-        let new_group = GroupState::new(self.group_counter, colour, coordinate); // create the group
-        new_group_map.insert(self.group_counter, new_group.clone());
-        new_groups[coordinate.get_index()] = Some(self.group_counter); // this can be changed later.
-        let mut surrounding_groups = BoardState::find_adjacent_groups(&new_groups, &new_group_map, coordinate, colour, self.size); // get all surrounding groups - 
+        let mut new_groups = self.groups.clone();
+        let mut new_group_map = self.group_map.clone();
+        let mut new_grid = self.grid.clone(); // these all get passed to the new board
+        let id = self.group_counter;
+
+        // sort the neighbours into liberties, groups to join and groups to attack
+        let mut liberties = HashSet::new();
+        let mut friendly_groups: Vec<usize> = Vec::new();
+        let mut opposing_groups: Vec<usize> = Vec::new();
+
+        for adjacent in BoardState::get_adjacent_indices(coordinate) {
+            match self.groups[adjacent.get_index()] {
+                None => {
+                    liberties.insert(adjacent);
+                }
+                Some(group_id) => {
+                    let neighbours = if self.group_map[&group_id].colour == colour { &mut friendly_groups } else { &mut opposing_groups };
+                    if !neighbours.contains(&group_id) {
+                        neighbours.push(group_id);
+                    }
+                }
+            }
+        }
+
+        // the stone joins any groups of its colour, which then lose the point as a liberty
+        let new_group = GroupState::new(id, colour, coordinate, liberties);
+        let mut joined: Vec<&GroupState> = friendly_groups.iter().map(|group_id| &self.group_map[group_id]).collect();
+        joined.push(&new_group);
+        let mut merged_group = GroupState::merge_groups(id, &joined);
+        merged_group.liberties.remove(&coordinate);
+
+        for group_id in &friendly_groups {
+            new_group_map.remove(group_id);
+        }
+        for point in merged_group.get_points() { // update group list
+            new_groups[point.get_index()] = Some(id);
+        }
+        new_grid[coordinate.get_index()] = colour;
+        new_group_map.insert(id, merged_group);
 
-        if !surrounding_groups.is_empty() { // ie there exist groups to merge
-            surrounding_groups.push(&new_group);
-            let merged_group = GroupState::merge_groups(self.group_counter, &surrounding_groups);
+        // the opposing groups lose the point as a liberty, and are captured if it was their last
+        let mut captured_points: Vec<Coordinate> = Vec::new();
+        for group_id in opposing_groups {
+            let group = new_group_map.get_mut(&group_id).unwrap();
+            group.liberties.remove(&coordinate);
 
-            for point in merged_group.get_positions() { // update group list
-                new_groups[point.get_index()] = Some(self.group_counter);
+            if !group.check_liberties() {
+                captured_points.extend(new_group_map.remove(&group_id).unwrap().points);
             }
-            new_group_map.insert(self.group_counter, merged_group);
         }
 
-        let (new_groups, flag) = BoardState::check_opposing_adjacent_liberties(&new_groups, &new_group_map, self.size, coordinate);
+        BoardState::clear_points(&mut new_groups, &mut new_group_map, &mut new_grid, &captured_points);
+        let mut prisoners = self.get_prisoners_after(colour, captured_points.len());
+
+        // the hash is updated with just the stones that changed
+        let mut hash = self.zobrist_table.get_position() ^ zobrist::stone_key(coordinate.get_index(), colour);
+        for point in &captured_points {
+            hash ^= zobrist::stone_key(point.get_index(), colour.swap_turn());
+        }
 
-        let final_grid = &BoardState::generate_grid_from_groups(&new_groups, &new_group_map, self.size);
+        // a single stone that took a single stone and is left in atari can be taken straight back
+        let new_group = &new_group_map[&id];
+        let ko_point = match captured_points[..] {
+            [captured] if new_group.get_points().len() == 1 && new_group.in_atari() => Some(captured.get_index()),
+            _ => None,
+        };
 
-        if flag { // no groups were removed 
-            let final_group = new_group_map.get(&new_groups[coordinate.get_index()].unwrap_or_else(|| panic!("Failed to unwrap new_groups at index {}", coordinate.get_index()))).unwrap_or_else(|| panic!("Failed to unwrap new_group_map"));
-            //println!("liberties: {:?}", final_group.clone().calculate_liberties(final_grid, self.size));            
-            if !final_group.check_liberties(final_grid, self.size) { // final merged group has no liberties and didnt remove any other group
+        if captured_points.is_empty() && !new_group_map[&id].check_liberties() { // the merged group has no liberties and didnt remove any other group
+            if !self.rules.suicide_allowed || new_group_map[&id].get_points().len() == 1 {
                 return Err(TurnErrors::Suicide);
             }
+
+            // the group captures itself, giving the opponent its stones
+            let suicided: Vec<Coordinate> = new_group_map.remove(&id).unwrap().points.into_iter().collect();
+            prisoners[colour.swap_turn().into_usize() - 1] += suicided.len();
+            for point in &suicided {
+                hash ^= zobrist::stone_key(point.get_index(), colour);
+            }
+            BoardState::clear_points(&mut new_groups, &mut new_group_map, &mut new_grid, &suicided);
         }
 
-        if self.zobrist_table.contains_position(&final_grid) { // check for repeated position/ko
+        if self.zobrist_table.contains_position(hash, colour, self.rules.ko) { // check for repeated position/ko
             return Err(TurnErrors::Ko);
         }
 
-        new_zobrist_table.insert_position(&final_grid);
-        let final_group_map = BoardState::clean_map(new_group_map, &new_groups);
+        let mut new_zobrist_table = self.zobrist_table.clone();
+        new_zobrist_table.insert_position(hash, colour, ko_point);
 
         Ok(BoardState {
             size: self.size,
-            groups: new_groups.to_owned(),
-            group_map: final_group_map,
+            groups: new_groups,
+            group_map: new_group_map,
             group_counter: self.group_counter + 1,
             zobrist_table: new_zobrist_table,
+            prisoners,
+            rules: self.rules,
+            grid: new_grid,
         })
     }
 
-    pub fn clean_map(group_map: HashMap<usize, GroupState>, groups: &Vec<Option<usize>>) -> HashMap<usize, GroupState> {
-        let current_groups: HashSet<usize> = groups.clone().into_iter().filter_map(|x| x).collect();
-        let new = group_map.clone().into_iter().filter(|(key, _)| current_groups.contains(key)).collect();
-        return new;
+    /// empties the points of groups that were taken off the board, giving the groups around them the liberties back
+    /// 
+    /// the groups must already be removed from the map
+    fn clear_points(groups: &mut [Option<usize>], group_map: &mut HashMap<usize, GroupState>, grid: &mut [Colour], points: &[Coordinate]) {
+        for point in points {
+            groups[point.get_index()] = None;
+            grid[point.get_index()] = Colour::Empty;
+        }
+
+        for &point in points {
+            for adjacent in BoardState::get_adjacent_indices(point) {
+                if let Some(group) = groups[adjacent.get_index()].and_then(|group_id| group_map.get_mut(&group_id)) {
+                    group.liberties.insert(point);
+                }
+            }
+        }
+    }
+
+    /// returns the prisoners once the given colour has captured more stones
+    fn get_prisoners_after(&self, colour: Colour, captured: usize) -> [usize; 2] {
+        let mut prisoners = self.prisoners;
+        prisoners[colour.into_usize() - 1] += captured;
+        prisoners
+    }
+
+    /// returns the hash of the position, including the colour to play and any ko, which is the same for equal positions
+    pub fn hash(&self) -> u64 {
+        self.zobrist_table.get_hash()
+    }
+
+    /// returns the board after the given colour passes
+    pub fn pass(&self, colour: Colour) -> BoardState {
+        let mut board = self.clone();
+        board.zobrist_table.insert_pass(colour);
+        board
     }
 }
 
 
 // pure state
 impl BoardState {
-    pub fn get_adjacent_indices(size: usize, coordinate: Coordinate) -> Vec<Coordinate> {
+    /// returns the adjacent coordinates on the board the coordinate belongs to
+    pub fn get_adjacent_indices(coordinate: Coordinate) -> Vec<Coordinate> {
+        let size = coordinate.get_size();
         let (x, y) = coordinate.get_position();
         let mut indices = Vec::new();
 
         if x > 0 {
-            indices.push(Coordinate::Index((x - 1) * size + y)); // Top
+            indices.push(Coordinate::Index((x - 1) * size + y, size)); // Top
         }
         if y > 0 {
-            indices.push(Coordinate::Index(x * size + y - 1)); // Left
+            indices.push(Coordinate::Index(x * size + y - 1, size)); // Left
         }
         if x < size - 1 {
-            indices.push(Coordinate::Index((x + 1) * size + y)); // Bottom
+            indices.push(Coordinate::Index((x + 1) * size + y, size)); // Bottom
         }
         if y < size - 1 {
-            indices.push(Coordinate::Index(x * size + y + 1)); // Right
+            indices.push(Coordinate::Index(x * size + y + 1, size)); // Right
         }
         indices
     }
 
     /// return a list of all current groups actually on the board
-    pub fn _get_current_groups(&self) -> Vec<GroupState> {
+    pub fn get_current_groups(&self) -> Vec<GroupState> {
         let current_groups: HashSet<GroupState> = self.groups.clone()
                                                     .into_iter()
                                                     .filter_map(|x| x.and_then(|key| self.group_map.get(&key)))
                                                     .cloned()
                                                     .collect();
 
-        return current_groups.into_iter().collect();
+        current_groups.into_iter().collect()
     }
 
     /// return if the group is currently on the board
@@ -177,11 +257,11 @@ impl BoardState {
                                                     .filter_map(|x| x.and_then(|key| self.group_map.get(&key)))
                                                     .cloned()
                                                     .collect();
-        current_groups.contains(&group)
+        current_groups.contains(group)
     }
 
     /// returns the group at the given coordinate
-    pub fn _find_group(&self, coordinate: Coordinate) -> Option<&GroupState> {
+    pub fn find_group(&self, coordinate: Coordinate) -> Option<&GroupState> {
         let index = coordinate.get_index();
 
         if let Some(group_id) = self.groups[index] {
@@ -195,84 +275,18 @@ impl BoardState {
         self.groups[coordinate.get_index()].is_none()
     }
 
-    /// return a list of adjacent same colour groups given a specific coordinate
-/// return a list of adjacent same colour groups given a specific coordinate
-    pub fn find_adjacent_groups<'a>(groups: &'a Vec<Option<usize>>, group_map: &'a HashMap<usize, GroupState>, coordinate: Coordinate, group_colour: Colour, size: usize) -> Vec<&'a GroupState> {
-        let group_id = groups[coordinate.get_index()].expect("Group ID cannot be None");
-        let group = group_map.get(&group_id).expect("Group not found in group_map");
-
-        let surrounding_groups: HashSet<&GroupState> = group
-            .get_positions()
-            .iter()
-            .flat_map(|point| BoardState::get_adjacent_indices(size, *point))
-            .filter(|&adjacent_point| !group.get_points().contains(&adjacent_point))
-            .filter_map(|adjacent_point| {
-                let index = groups[adjacent_point.get_index()]?;
-                let group_data = group_map.get(&index)?;
-                if group_data.get_colour() == group_colour {
-                    Some(group_data)
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        surrounding_groups.into_iter().collect()
-    }
-
-    /// check and remove the opposing adjacent 0 liberty groups
-    pub fn check_opposing_adjacent_liberties(total_groups: &Vec<Option<usize>>, map: &HashMap<usize, GroupState>, size: usize, position: Coordinate) -> (Vec<Option<usize>>, bool) {
-        let id = total_groups[position.get_index()].expect("Wrong position given");
-        let group = map.get(&id).expect("Wrong id provided");
-        let group_colour = group.get_colour();
-        let grid = BoardState::generate_grid_from_groups(&total_groups, &map, size);
-
-        // we need to get adjacent positions
-        // check if a group with the opposite colour is there
-        // add that group in to be checked
-        // if that checked group is at 0 liberties call the remove
-
-        let mut groups_to_check: Vec<usize> = Vec::new();
-        let mut groups_to_remove: Vec<usize> = Vec::new();
-        let mut didnt_remove_groups = true;
-
-        // for each position in the main group
-        // check that position doesnt contain a group of the opposite colour
-
-        for point in group.get_positions() {
-            let adjacent = BoardState::get_adjacent_indices(size, point);
-            for adjacent_point in adjacent {
-                if let Some(group_id) = total_groups[adjacent_point.get_index()] {
-                    if map.get(&group_id).expect("Group not in Map!").get_colour() != group_colour { // this group is the opposite colour to our new group
-                        groups_to_check.push(group_id);
-                    }
-                }
-            }
-        }
-
-        for group_id in groups_to_check { // find the groups that need to be removed
-            if !map.get(&group_id).unwrap().check_liberties(&grid, size) { // if a checked group has 0 liberties
-                groups_to_remove.push(group_id);
-                didnt_remove_groups = false;
-            }
-        }
-
-        (BoardState::remove_groups(total_groups, groups_to_remove), didnt_remove_groups)
-    }
-
-    pub fn _debug_groups(&self, groups: &Vec<Option<usize>>, map: &HashMap<usize, GroupState>) {
-        let grid = BoardState::generate_grid_from_groups(groups, map, self.size);
+    pub fn _debug_groups(&self) {
         println!("-- Total Groups --");
-        for (id, group) in map {
+        for (id, group) in &self.group_map {
             let points = group.get_positions();
-            let liberties = group.calculate_liberties(&grid, self.size);
+            let liberties = group.calculate_liberties();
             let colour = group.colour;
             println!("Group {}: {:#?} with {:} positions: {:?} has {} liberties", id, colour, points.len(), points, liberties);
         }
     }
 
     pub fn debug_selection(&self, coordinate: Coordinate) {
-        if self.groups[coordinate.get_index()] == None {
+        if self.groups[coordinate.get_index()].is_none() {
             println!("Error! This is an Empty Square");
             return;
         }
@@ -282,49 +296,19 @@ impl BoardState {
 
         println!("-- GROUP INFO --");
         let points = group.get_positions();
-        let liberties = group.calculate_liberties(&self.get_grid(), self.size);
+        let liberties = group.calculate_liberties();
         let colour = group.colour;
         println!("Group {}: {:#?} with positions: {:?} has {} liberties", id, colour, points, liberties);
     }
 
-    /// Assuming game finished (and no dead stones) this will return how many empty spaces are enclosed by a particular colour
-    pub fn get_surrounded_area(&self, grid: &Vec<Colour>, colour: Colour) -> usize {
-        let mut empty_locations: HashSet<Coordinate> = HashSet::new();
-        let groups: Vec<&GroupState> = self.group_map.values().filter(|&group| group.colour == colour).collect();
-    
-        let mut queue: Vec<Coordinate> = groups
-            .iter()
-            .flat_map(|group| group.get_positions())
-            .flat_map(|coordinate| BoardState::get_adjacent_indices(self.size, coordinate))
-            .collect();
-    
-        queue.retain(|&adjacent_coord| {
-            let index = adjacent_coord.get_index();
-            grid.get(index) == Some(&Colour::Empty)
-        });
-    
-        while let Some(position) = queue.pop() {
-            if grid[position.get_index()] == Colour::Empty && empty_locations.insert(position) {
-                let neighbours: Vec<Coordinate> = BoardState::get_adjacent_indices(self.size, position)
-                    .iter()
-                    .filter(|position| grid[position.get_index()] == Colour::Empty)
-                    .copied()
-                    .collect();
-                queue.extend(neighbours);
-            }
-        }
-    
-        empty_locations.len()
-    }
-
-    pub fn get_colour_territory(&self, grid: &Vec<Colour>) -> (usize, usize) {
+    pub fn _get_colour_territory(&self, grid: &[Colour]) -> (usize, usize) {
         let mut empty_locations: [HashSet<Coordinate>; 2] = [HashSet::new(), HashSet::new()];
         let groups: Vec<&GroupState> = self.group_map.values().collect();
     
         let mut queue: Vec<(Colour, Coordinate)> = groups
             .iter()
             .flat_map(|group| group.get_positions().into_iter().map(move |pos| (group.colour, pos)))
-            .flat_map(|(colour, coordinate)| BoardState::get_adjacent_indices(self.size, coordinate).into_iter().map(move |pos| (colour, pos)))
+            .flat_map(|(colour, coordinate)| BoardState::get_adjacent_indices(coordinate).into_iter().map(move |pos| (colour, pos)))
             .collect();
     
         queue.retain(|&(_, adjacent_coord)| {
@@ -334,7 +318,7 @@ impl BoardState {
     
         while let Some((colour, position)) = queue.pop() {
             if grid[position.get_index()] == Colour::Empty && empty_locations[colour.into_usize() - 1].insert(position) {
-                let neighbours: Vec<(Colour, Coordinate)> = BoardState::get_adjacent_indices(self.size, position)
+                let neighbours: Vec<(Colour, Coordinate)> = BoardState::get_adjacent_indices(position)
                     .iter()
                     .filter(|position| grid[position.get_index()] == Colour::Empty)
                     .map(|&position| (colour, position))
@@ -354,11 +338,11 @@ impl BoardState {
             .iter()
             .enumerate()
             .filter(|(_, value)| value == &&Colour::Empty)
-            .map(|(index, _)| Coordinate::Index(index))
+            .map(|(index, _)| Coordinate::Index(index, self.size))
             .collect();
 
         for position in empty_points {
-            empty_groups.insert(self.create_empty_group(&grid, position)); // inefficient implementation - this goes through every empty point and constructs a group from it's surrounding neighbours
+            empty_groups.insert(self.create_empty_group(grid, position)); // inefficient implementation - this goes through every empty point and constructs a group from it's surrounding neighbours
         } // after this we now have every empty group on the board
 
         let mut surrounded_territory: Vec<GroupState> = Vec::new(); // surrounded territory of a particular colour
@@ -367,13 +351,12 @@ impl BoardState {
             let mut adjacents: HashSet<Colour> = HashSet::new();
 
             for empty_point in empty_group.get_positions() {
-                let a = BoardState::get_adjacent_indices(self.size, empty_point);
+                let a = BoardState::get_adjacent_indices(empty_point);
                 adjacents.extend(a.iter().map(|&coord| grid[coord.get_index()]));
             }
 
-            if adjacents.contains(&colour.swap_turn()) {
-                continue;
-            } else {
+            // a region only counts when it touches the colour and nothing else, so an empty board is nobody's
+            if adjacents.contains(&colour) && !adjacents.contains(&colour.swap_turn()) {
                 surrounded_territory.push(empty_group);
             }
         }
@@ -390,8 +373,8 @@ impl BoardState {
         for (index, colour) in grid.iter().enumerate() {
             match colour {
                 Colour::Empty => {
-                    if !visited.contains(&Coordinate::Index(index)) {
-                        let empty_group = self.create_empty_group(&grid, Coordinate::Index(index));
+                    if !visited.contains(&Coordinate::Index(index, self.size)) {
+                        let empty_group = self.create_empty_group(&grid, Coordinate::Index(index, self.size));
                         visited.extend(empty_group.get_positions());
                         empty_points.insert(empty_group);
                     }
@@ -407,7 +390,7 @@ impl BoardState {
         let is_all_played = empty_points.par_iter().all(|empty_group| {
             let adjacents: HashSet<Colour> = empty_group.get_positions()
                 .into_iter()
-                .flat_map(|empty_point| BoardState::get_adjacent_indices(self.size, empty_point))
+                .flat_map(BoardState::get_adjacent_indices)
                 .map(|coord| grid[coord.get_index()])
                 .collect();
     
@@ -417,6 +400,28 @@ impl BoardState {
         is_all_played
     }
 
+    /// returns the board with the given (dead) stones taken off, which count as prisoners for the opponent
+    pub fn remove_stones(&self, stones: &HashSet<Coordinate>) -> BoardState {
+        if stones.is_empty() {
+            return self.clone();
+        }
+
+        let mut grid = self.get_grid();
+        let mut prisoners = self.prisoners;
+        for stone in stones {
+            let colour = grid[stone.get_index()];
+            if colour != Colour::Empty {
+                prisoners[colour.swap_turn().into_usize() - 1] += 1;
+                grid[stone.get_index()] = Colour::Empty;
+            }
+        }
+
+        let mut board = BoardState::from_colours(&grid, self.size, self.zobrist_table.get_to_move()).expect("the grid is the size of this board");
+        board.prisoners = prisoners;
+        board.rules = self.rules;
+        board
+    }
+
     /// Goes through all adjacent points to create a group of empty "territory"
     /// 
     /// This is a helper function for check_all_important_points_played() in order to build up the empty groups
@@ -426,7 +431,7 @@ impl BoardState {
 
         while let Some(position) = queue.pop() {
             if !points.contains(&position) {
-                let adjacent_empty_points: Vec<Coordinate> = BoardState::get_adjacent_indices(self.size, position).iter().filter(|p| grid[p.get_index()] == Colour::Empty)
+                let adjacent_empty_points: Vec<Coordinate> = BoardState::get_adjacent_indices(position).iter().filter(|p| grid[p.get_index()] == Colour::Empty)
                     .map(|c| c.to_owned()).collect();
 
                 queue.extend(adjacent_empty_points);
@@ -436,20 +441,265 @@ impl BoardState {
         GroupState::from_empty_points(points)
     }
 
-    /// Recreates a board state given a slice of colours by sequentially playing a given move.
+    /// Returns the board with the given stones set up on it and the given colour to play
     /// 
-    /// This will only ever be called for scoring so there is no need to worry about ko's and previous board
-    /// iterations.
-    pub fn from_colours(colours: &[Colour], size: usize) -> Self {
-        let mut board_state = BoardState::new(size);
+    /// unlike playing them, setting stones up leaves no ko or history behind, so the new position starts its own line.
+    /// each point can only be given once, and every group has to be left with a liberty
+    pub fn setup_stones(&self, stones: &[(Coordinate, Colour)], to_move: Colour) -> Result<BoardState, TurnErrors> {
+        let mut grid = self.get_grid();
+        for &(coordinate, colour) in stones {
+            if coordinate.get_size() != self.size || !coordinate.in_bounds() {
+                return Err(TurnErrors::OutofBounds);
+            }
+            if grid[coordinate.get_index()] != Colour::Empty {
+                return Err(TurnErrors::AlreadyPlaced);
+            }
+            grid[coordinate.get_index()] = colour;
+        }
 
-        for (i, &colour) in colours.iter().enumerate() {
-            if colour != Colour::Empty {
-                let coordinate = Coordinate::Index(i);
-                board_state = board_state.add_stone(coordinate, colour).unwrap();
+        let mut board = BoardState::from_colours(&grid, self.size, to_move).expect("the grid is the size of this board");
+        if board.group_map.values().any(|group| !group.check_liberties()) {
+            return Err(TurnErrors::Suicide);
+        }
+        board.rules = self.rules;
+        board.prisoners = self.prisoners;
+        Ok(board)
+    }
+
+    /// Creates a board state with the given colour on each point and the given colour to play
+    /// 
+    /// the groups are found directly rather than by playing the stones, so the position is the only one in its history
+    pub fn from_colours(colours: &[Colour], size: usize, to_move: Colour) -> Result<Self, BoardErrors> {
+        if size == 0 || size > MAX_BOARD_SIZE || colours.len() != size * size {
+            return Err(BoardErrors::InvalidSize(size));
+        }
+
+        let mut groups = vec![None; size * size];
+        let mut group_map = HashMap::new();
+        let mut group_counter = 0;
+
+        for (index, &colour) in colours.iter().enumerate() {
+            if colour == Colour::Empty || groups[index].is_some() {
+                continue;
             }
+
+            // flood fill the stones connected to this one, collecting the empty points around them
+            let mut points = HashSet::new();
+            let mut liberties = HashSet::new();
+            let mut queue = vec![Coordinate::Index(index, size)];
+            while let Some(point) = queue.pop() {
+                if !points.insert(point) {
+                    continue;
+                }
+                groups[point.get_index()] = Some(group_counter);
+
+                for adjacent in BoardState::get_adjacent_indices(point) {
+                    match colours[adjacent.get_index()] {
+                        Colour::Empty => {
+                            liberties.insert(adjacent);
+                        }
+                        neighbour if neighbour == colour => queue.push(adjacent),
+                        _ => {}
+                    }
+                }
+            }
+
+            let mut group = GroupState::new(group_counter, colour, Coordinate::Index(index, size), liberties);
+            group.points = points;
+            group_map.insert(group_counter, group);
+            group_counter += 1;
+        }
+
+        Ok(BoardState {
+            size,
+            groups,
+            group_map,
+            group_counter,
+            zobrist_table: ZobristTable::from_stones(size, colours, to_move),
+            prisoners: [0, 0],
+            rules: Rules::default(),
+            grid: colours.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::KoRule;
+
+    /// plays the stones in order from an empty board, alternating from black
+    fn play(size: usize, moves: &[usize]) -> BoardState {
+        let mut board = BoardState::new(size).unwrap();
+        let mut colour = Colour::Black;
+        for &index in moves {
+            board = board.add_stone(Coordinate::Index(index, size), colour).unwrap();
+            colour = colour.swap_turn();
         }
+        board
+    }
+
+    /// returns the liberties of the group on the point, counted from the grid
+    fn counted_liberties(board: &BoardState, point: Coordinate) -> HashSet<Coordinate> {
+        let grid = board.get_grid();
+        board.find_group(point).unwrap().get_points().iter()
+            .flat_map(|&point| BoardState::get_adjacent_indices(point))
+            .filter(|adjacent| grid[adjacent.get_index()] == Colour::Empty)
+            .collect()
+    }
+
+    #[test]
+    fn single_stones_count_their_liberties() {
+        let board = play(5, &[12, 0, 2]);
+        assert_eq!(board.find_group(Coordinate::Index(12, 5)).unwrap().calculate_liberties(), 4);
+        assert_eq!(board.find_group(Coordinate::Index(0, 5)).unwrap().calculate_liberties(), 2);
+        assert_eq!(board.find_group(Coordinate::Index(2, 5)).unwrap().calculate_liberties(), 3);
+    }
+
+    #[test]
+    fn merged_groups_share_their_liberties() {
+        // black 6 and 8 are joined by black 7, with white 2 and 12 taking liberties from the group
+        let board = play(5, &[6, 2, 8, 12, 7]);
+        let group = board.find_group(Coordinate::Index(7, 5)).unwrap();
 
-        board_state
+        assert_eq!(group.get_points().len(), 3);
+        assert_eq!(group.get_liberties(), &counted_liberties(&board, Coordinate::Index(7, 5)));
+        assert_eq!(group.calculate_liberties(), 6);
+        assert_eq!(board.find_group(Coordinate::Index(6, 5)).unwrap().get_points().len(), 3);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn captures_give_liberties_back() {
+        // white 1 and 2 in the corner are captured by black 3, 6 and 7
+        let board = play(5, &[0, 1, 6, 2, 7, 24, 3]);
+        assert_eq!(board.get_colour(Coordinate::Index(1, 5)), Colour::Empty);
+        assert_eq!(board.get_colour(Coordinate::Index(2, 5)), Colour::Empty);
+        assert_eq!(board.prisoners, [2, 0]);
+
+        for point in [0, 3, 6, 7] {
+            let point = Coordinate::Index(point, 5);
+            assert_eq!(board.find_group(point).unwrap().get_liberties(), &counted_liberties(&board, point));
+        }
+        assert!(board.find_group(Coordinate::Index(0, 5)).unwrap().get_liberties().contains(&Coordinate::Index(1, 5)));
+    }
+
+    #[test]
+    fn liberties_match_the_grid_through_a_random_game() {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let mut board = BoardState::new(7).unwrap();
+        let mut colour = Colour::Black;
+
+        for _ in 0..300 {
+            let point = Coordinate::Index(rng.gen_range(0..49), 7);
+            if let Ok(next) = board.add_stone(point, colour) {
+                board = next;
+                colour = colour.swap_turn();
+            }
+
+            for group in board.get_current_groups() {
+                let point = *group.get_points().iter().next().unwrap();
+                assert_eq!(group.get_liberties(), &counted_liberties(&board, point));
+            }
+        }
+    }
+
+    /// sets up a ko with the given rule, returning the board after black takes it with 6
+    fn take_ko(ko: KoRule) -> BoardState {
+        let mut board = BoardState::new(5).unwrap();
+        board.rules = Rules { ko, ..Rules::default() };
+        for (point, colour) in [(2, Colour::Black), (1, Colour::White), (8, Colour::Black), (5, Colour::White), (12, Colour::Black), (11, Colour::White), (24, Colour::Black), (7, Colour::White), (6, Colour::Black)] {
+            board = board.add_stone(Coordinate::Index(point, 5), colour).unwrap();
+        }
+        assert_eq!(board.get_colour(Coordinate::Index(7, 5)), Colour::Empty);
+        board
+    }
+
+    #[test]
+    fn ko_cant_be_retaken_straight_away() {
+        for ko in [KoRule::Simple, KoRule::PositionalSuperko, KoRule::SituationalSuperko] {
+            let board = take_ko(ko);
+            assert!(matches!(board.add_stone(Coordinate::Index(7, 5), Colour::White), Err(TurnErrors::Ko)));
+        }
+    }
+
+    #[test]
+    fn simple_ko_can_be_retaken_after_passes() {
+        let board = take_ko(KoRule::Simple).pass(Colour::White).pass(Colour::Black);
+        assert!(board.add_stone(Coordinate::Index(7, 5), Colour::White).is_ok());
+    }
+
+    #[test]
+    fn superko_stops_a_retake_after_passes() {
+        // retaking recreates the position white played into before black took the ko
+        for ko in [KoRule::PositionalSuperko, KoRule::SituationalSuperko] {
+            let board = take_ko(ko).pass(Colour::White).pass(Colour::Black);
+            assert!(matches!(board.add_stone(Coordinate::Index(7, 5), Colour::White), Err(TurnErrors::Ko)));
+        }
+    }
+
+    #[test]
+    fn ko_can_be_retaken_after_moves_elsewhere() {
+        for ko in [KoRule::Simple, KoRule::PositionalSuperko, KoRule::SituationalSuperko] {
+            let board = take_ko(ko)
+                .add_stone(Coordinate::Index(20, 5), Colour::White).unwrap()
+                .add_stone(Coordinate::Index(22, 5), Colour::Black).unwrap();
+            assert!(board.add_stone(Coordinate::Index(7, 5), Colour::White).is_ok());
+        }
+    }
+
+    #[test]
+    fn an_empty_board_is_nobodys_territory() {
+        let board = BoardState::new(5).unwrap();
+        let grid = board.get_grid();
+        assert!(board.get_territory(&grid, Colour::Black).is_empty());
+        assert!(board.get_territory(&grid, Colour::White).is_empty());
+    }
+
+    #[test]
+    fn territory_is_only_bordered_by_one_colour() {
+        // a black wall on the second column and a white one on the fourth
+        let mut colours = vec![Colour::Empty; 25];
+        for row in 0..5 {
+            colours[row * 5 + 1] = Colour::Black;
+            colours[row * 5 + 3] = Colour::White;
+        }
+        let board = BoardState::from_colours(&colours, 5, Colour::Black).unwrap();
+        let grid = board.get_grid();
+
+        let count = |colour| GroupState::combine_groups(&board.get_territory(&grid, colour)).len();
+        assert_eq!(count(Colour::Black), 5);
+        assert_eq!(count(Colour::White), 5); // the middle column touches both, so it's neither's
+    }
+
+    #[test]
+    fn boards_larger_than_the_keys_are_rejected() {
+        assert!(BoardState::new(MAX_BOARD_SIZE).is_ok());
+        assert!(matches!(BoardState::new(MAX_BOARD_SIZE + 1), Err(BoardErrors::InvalidSize(_))));
+        assert!(matches!(BoardState::new(0), Err(BoardErrors::InvalidSize(0))));
+        assert!(BoardState::from_colours(&[Colour::Empty; 8], 3, Colour::Black).is_err());
+    }
+
+    #[test]
+    fn from_colours_matches_played_position() {
+        // black captures the white stone on 1 with the stone on 2
+        let board = play(3, &[0, 1, 4, 8, 2]);
+        let rebuilt = BoardState::from_colours(&board.get_grid(), 3, Colour::White).unwrap();
+
+        assert_eq!(rebuilt.hash(), board.hash());
+        assert_eq!(rebuilt.get_grid(), board.get_grid());
+        assert_eq!(rebuilt.find_group(Coordinate::Index(0, 3)).unwrap().calculate_liberties(), 2);
+        assert_eq!(rebuilt.find_group(Coordinate::Index(8, 3)).unwrap().calculate_liberties(), 2);
+    }
+
+    #[test]
+    fn from_colours_starts_a_new_history() {
+        let board = play(3, &[0, 1, 4]);
+        let rebuilt = BoardState::from_colours(&board.get_grid(), 3, Colour::White).unwrap();
+
+        // the empty board was never played on the rebuilt board, so superko doesn't stop anything
+        assert!(!rebuilt.zobrist_table.contains_position(BoardState::new(3).unwrap().zobrist_table.get_position(), Colour::White, KoRule::PositionalSuperko));
+        assert!(rebuilt.zobrist_table.contains_position(rebuilt.zobrist_table.get_position(), Colour::White, KoRule::PositionalSuperko));
+    }
+}
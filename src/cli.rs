@@ -0,0 +1,221 @@
+// parsing the command line arguments
+use go_rs::board_state::MAX_BOARD_SIZE;
+use go_rs::colour::Colour;
+use go_rs::config::SearchConfig;
+use go_rs::fails::ConfigErrors;
+use go_rs::game_state::GameState;
+use go_rs::player::PlayerKind;
+use go_rs::rules::Rules;
+
+pub const USAGE: &str = "\
+usage: go-rs [mode] [flags]
+
+modes:
+    gui                 play in a window (the default)
+    gtp                 run as a GTP v2 engine over stdin/stdout
+    selfplay            have the bot play against another configuration of itself
+    match               have the bot play against a random player (or --opponent)
+    analyze <file.sgf>  print the bot's candidate moves and the lines it expects for the end of a game
+
+flags:
+    --size <n>          board size (default 5)
+    --komi <points>     komi, applied after --rules
+    --rules <name>      chinese, japanese, aga, new_zealand or tromp-taylor
+    --bot-colour <c>    the colour the bot plays: black, white or none (two humans in the gui)
+    --games <n>         how many games selfplay and match play
+    --player <name>     who the bot is: mcts (the default), random or gtp:<command>
+    --opponent <name>   who the bot plays: human, mcts, random or gtp:<command>
+                        (a human in the gui, mcts in selfplay and random in match by default)
+    --output <dir>      where selfplay and match write their games and summary (default selfplay)
+    --help              show this message
+
+search flags (--opponent-<flag> sets them for an mcts opponent, which otherwise shares the bot's):
+    --time <seconds>    time the bot searches for each move (default 5)
+    --iterations <n>    simulations the bot runs for each move (default 25000)
+    --nodes <n>         how big the bot's tree can grow (default 300000, about a gigabyte)
+    --threads <n>       simulations the bot runs at once (default 2)
+    --parallel <mode>   how the threads share the search: tree (one shared tree, the default)
+                        or root (a tree each, with their statistics added up to choose the move)
+    --exploration <c>   how much the bot explores moves that aren't winning (default 0.3)
+    --rave <visits>     how long the bot trusts the win rate of moves played later in its
+                        simulations (RAVE) before their own, 0 turns it off (default 1000)
+    --fpu <value>       how urgent unvisited moves are: infinite (the default), a win rate
+                        or parent-<reduction> for the win rate of the position less the reduction
+    --selection <name>  how the move is chosen: most-visits (the default), max-robust (most visits
+                        and the best win rate, searching longer until they agree), lcb (the best
+                        lower bound of the win rate) or win-rate
+    --resign <rate>     the win rate the bot resigns below, 0 (the default) never resigns
+    --resign-moves <n>  how many moves in a row the win rate must stay below it (default 3)
+    --seed <n>          seeds the bot so games can be repeated (with --threads 1)";
+
+#[derive(Debug)]
+pub enum CliErrors { // errors regarding the command line arguments
+    UnknownMode(String),
+    UnknownFlag(String),
+    MissingValue(String), // the flag that needed a value
+    InvalidValue(String, String), // the flag and the value it was given
+}
+
+impl std::fmt::Display for CliErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliErrors::UnknownMode(mode) => write!(f, "unknown mode '{}'", mode),
+            CliErrors::UnknownFlag(flag) => write!(f, "unknown flag '{}'", flag),
+            CliErrors::MissingValue(flag) => write!(f, "{} needs a value", flag),
+            CliErrors::InvalidValue(flag, value) => write!(f, "invalid value '{}' for {}", value, flag),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Gui,
+    Gtp,
+    SelfPlay,
+    Match,
+    Analyze,
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub mode: Mode,
+    pub size: usize,
+    pub rules: Rules,
+    pub search: SearchConfig,
+    pub bot_colour: Option<Colour>, // the colour the bot plays in the gui and in a match
+    pub games: usize,
+    pub player: PlayerKind, // who the bot is
+    pub opponent: PlayerKind, // who the bot plays
+    pub output: String, // the directory selfplay and match write to
+    pub file: Option<String>, // the sgf file to analyze
+    pub help: bool,
+}
+
+impl Options {
+    pub fn new(size: usize) -> Self {
+        Options {
+            mode: Mode::Gui,
+            size,
+            rules: Rules::default(),
+            search: SearchConfig::new(),
+            bot_colour: None,
+            games: 1,
+            player: PlayerKind::Mcts(SearchConfig::new()),
+            opponent: PlayerKind::Human,
+            output: String::from("selfplay"),
+            file: None,
+            help: false,
+        }
+    }
+
+    /// Parses the arguments (without the program name), starting from the given defaults
+    pub fn parse(mut self, args: impl IntoIterator<Item = String>) -> Result<Self, CliErrors> {
+        let mut args = args.into_iter().peekable();
+        let mut komi: Option<f32> = None; // komi overrides the komi of the rules, whatever order they are given in
+        let (mut player, mut opponent): (Option<String>, Option<String>) = (None, None); // read once the search parameters are known
+        let mut opponent_settings: Vec<(String, String)> = Vec::new(); // the opponent's search parameters that differ from the bot's
+
+        if let Some(mode) = args.next_if(|arg| !arg.starts_with('-')) {
+            self.mode = match mode.as_str() {
+                "gui" => Mode::Gui,
+                "gtp" => Mode::Gtp,
+                "selfplay" => Mode::SelfPlay,
+                "match" => Mode::Match,
+                "analyze" => Mode::Analyze,
+                _ => return Err(CliErrors::UnknownMode(mode)),
+            };
+        }
+
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--help" | "-h" => self.help = true,
+                "--size" => {
+                    self.size = parse_value(&flag, args.next())?;
+                    if !(2..=MAX_BOARD_SIZE).contains(&self.size) {
+                        return Err(CliErrors::InvalidValue(flag, self.size.to_string()));
+                    }
+                }
+                "--komi" => komi = Some(parse_value(&flag, args.next())?),
+                "--rules" => {
+                    let name = args.next().ok_or(CliErrors::MissingValue(flag.clone()))?;
+                    self.rules = Rules::from_name(&name).ok_or(CliErrors::InvalidValue(flag, name))?;
+                }
+                "--time" | "--iterations" | "--nodes" | "--threads" | "--parallel" | "--exploration" | "--rave" | "--fpu" | "--selection" | "--resign" | "--resign-moves" | "--seed" => {
+                    let value = args.next().ok_or(CliErrors::MissingValue(flag.clone()))?;
+                    self.search.set(&flag[2..], &value).map_err(|_| CliErrors::InvalidValue(flag, value))?;
+                }
+                "--bot-colour" | "--bot-color" => {
+                    let colour = args.next().ok_or(CliErrors::MissingValue(flag.clone()))?;
+                    self.bot_colour = match colour.to_lowercase().as_str() {
+                        "b" | "black" => Some(Colour::Black),
+                        "w" | "white" => Some(Colour::White),
+                        "none" => None,
+                        _ => return Err(CliErrors::InvalidValue(flag, colour)),
+                    };
+                }
+                "--player" => player = Some(args.next().ok_or(CliErrors::MissingValue(flag.clone()))?),
+                "--opponent" => opponent = Some(args.next().ok_or(CliErrors::MissingValue(flag.clone()))?),
+                _ if flag.starts_with("--opponent-") => {
+                    let value = args.next().ok_or(CliErrors::MissingValue(flag.clone()))?;
+                    opponent_settings.push((flag, value));
+                }
+                "--output" => self.output = args.next().ok_or(CliErrors::MissingValue(flag.clone()))?,
+                "--games" => self.games = parse_value(&flag, args.next())?,
+                _ if !flag.starts_with('-') && self.file.is_none() => self.file = Some(flag),
+                _ => return Err(CliErrors::UnknownFlag(flag)),
+            }
+        }
+
+        if let Some(komi) = komi {
+            self.rules.komi = komi;
+        }
+
+        let mut opponent_config = self.search;
+        for (flag, value) in opponent_settings {
+            opponent_config.set(&flag["--opponent-".len()..], &value).map_err(|error| match error {
+                ConfigErrors::UnknownParameter(_) => CliErrors::UnknownFlag(flag),
+                ConfigErrors::InvalidValue(..) => CliErrors::InvalidValue(flag, value),
+            })?;
+        }
+
+        // the gui is against a human, selfplay against another mcts and match against a random player unless told otherwise
+        let default_opponent = match self.mode {
+            Mode::Gui => "human",
+            Mode::Match => "random",
+            _ => "mcts",
+        };
+        let player = player.unwrap_or(String::from("mcts"));
+        let opponent = opponent.unwrap_or(String::from(default_opponent));
+
+        self.player = PlayerKind::from_name(&player, self.search).ok_or(CliErrors::InvalidValue(String::from("--player"), player.clone()))?;
+        self.opponent = PlayerKind::from_name(&opponent, opponent_config).ok_or(CliErrors::InvalidValue(String::from("--opponent"), opponent.clone()))?;
+
+        // only the gui has anyone to play as a human
+        if self.mode != Mode::Gui {
+            if matches!(self.player, PlayerKind::Human) {
+                return Err(CliErrors::InvalidValue(String::from("--player"), player));
+            }
+            if matches!(self.opponent, PlayerKind::Human) && self.mode != Mode::Gtp {
+                return Err(CliErrors::InvalidValue(String::from("--opponent"), opponent));
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// starts a game with the board size and rules that were given
+    pub fn new_game(&self) -> GameState {
+        let mut game = GameState::new(self.size).expect("the size is checked when it's parsed");
+        game.set_rules(self.rules);
+        if let Some(seed) = self.search.seed {
+            game.set_seed(seed);
+        }
+        game
+    }
+}
+
+/// parses the value given to a flag
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, CliErrors> {
+    let value = value.ok_or(CliErrors::MissingValue(flag.to_string()))?;
+    value.parse().map_err(|_| CliErrors::InvalidValue(flag.to_string(), value))
+}
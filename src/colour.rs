@@ -24,7 +24,7 @@ impl Colour {
         }
     }
 
-    pub fn into_usize(&self) -> usize {
+    pub fn into_usize(self) -> usize {
         match self {
             Colour::Black => 1,
             Colour::White => 2,
@@ -33,10 +33,13 @@ impl Colour {
     }
 }
 
+pub const WIN_POINTS: usize = 2; // what a win adds to the wins of a search, so a draw can add half
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Outcome {
     BlackWin,
     WhiteWin,
-    _Draw, // Not possible with komi
+    Draw, // equal scores, ie with a whole number komi
 }
 
 impl Outcome {
@@ -44,7 +47,7 @@ impl Outcome {
         match self {
             Outcome::BlackWin => String::from("Black Wins"),
             Outcome::WhiteWin => String::from("White Wins"),
-            Outcome::_Draw => String::from("Draw"),
+            Outcome::Draw => String::from("Draw"),
         }
     }
 
@@ -54,15 +57,24 @@ impl Outcome {
         } else if white_score > black_score {
             Outcome::WhiteWin
         } else {
-            Outcome::_Draw
+            Outcome::Draw
+        }
+    }
+
+    /// returns what the outcome adds to the wins of the given colour: WIN_POINTS for a win and half that for a draw
+    pub fn get_points(self, colour: Colour) -> usize {
+        match self {
+            Outcome::Draw => WIN_POINTS / 2,
+            outcome if outcome.into_colour() == colour => WIN_POINTS,
+            _ => 0,
         }
     }
 
-    pub fn into_colour(&self) -> Colour {
+    pub fn into_colour(self) -> Colour {
         match self {
             Outcome::BlackWin => Colour::Black,
             Outcome::WhiteWin => Colour::White,
-            Outcome::_Draw => Colour::Empty,
+            Outcome::Draw => Colour::Empty,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,208 @@
+// the parameters of the MCTS
+use std::time::Duration;
+
+use crate::fails::ConfigErrors;
+
+/// how the threads of the MCTS share their work
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parallelism {
+    Tree, // every thread searches the same tree
+    Root, // each thread searches its own tree from the same root, and the statistics of the root's children are added up
+}
+
+impl Parallelism {
+    pub fn from_name(name: &str) -> Option<Parallelism> {
+        match name.to_lowercase().as_str() {
+            "tree" => Some(Parallelism::Tree),
+            "root" => Some(Parallelism::Root),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Parallelism::Tree => "tree",
+            Parallelism::Root => "root",
+        }
+    }
+}
+
+/// the value UCT gives a move that hasn't been visited yet (first play urgency)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fpu {
+    Infinite, // every move is tried once before any is tried again
+    Value(f64), // unvisited moves count as having this win rate
+    Reduction(f64), // unvisited moves count as the parent's win rate less this
+}
+
+impl Fpu {
+    /// reads "infinite", a win rate ie "0.5", or "parent-" and a reduction ie "parent-0.2"
+    pub fn from_name(name: &str) -> Option<Fpu> {
+        let name = name.to_lowercase();
+        if name == "infinite" {
+            return Some(Fpu::Infinite);
+        }
+        if let Some(reduction) = name.strip_prefix("parent") {
+            let reduction = reduction.strip_prefix('-').unwrap_or(reduction);
+            return if reduction.is_empty() { Some(Fpu::Reduction(0.0)) } else { reduction.parse().ok().map(Fpu::Reduction) };
+        }
+        name.parse().ok().map(Fpu::Value)
+    }
+
+    pub fn get_name(&self) -> String {
+        match self {
+            Fpu::Infinite => String::from("infinite"),
+            Fpu::Value(value) => value.to_string(),
+            Fpu::Reduction(reduction) => format!("parent-{}", reduction),
+        }
+    }
+}
+
+/// how the move is chosen from the root's children once the search is over
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Selection {
+    MostVisits, // the robust child
+    MaxRobust, // the most visited move once it also has the best win rate, searching for up to half as long again until it does
+    Lcb, // the best lower confidence bound of the win rate, so a good move needs enough visits to be sure of
+    WinRate, // the best win rate, which can be a move that was only visited a few times
+}
+
+impl Selection {
+    pub fn from_name(name: &str) -> Option<Selection> {
+        match name.to_lowercase().as_str() {
+            "most-visits" | "visits" => Some(Selection::MostVisits),
+            "max-robust" => Some(Selection::MaxRobust),
+            "lcb" => Some(Selection::Lcb),
+            "win-rate" | "winrate" => Some(Selection::WinRate),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Selection::MostVisits => "most-visits",
+            Selection::MaxRobust => "max-robust",
+            Selection::Lcb => "lcb",
+            Selection::WinRate => "win-rate",
+        }
+    }
+}
+
+pub const LAG_MARGIN: Duration = Duration::from_millis(200); // kept back from every move the clock limits, for the time taken to send it
+
+/// the clock of a game, as given by the gtp time settings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeControl {
+    None, // only the search's own max_time limits each move
+    Absolute(Duration), // the main time for the whole game
+    ByoYomi(Duration, Duration, usize), // main time, then the number of periods of the given length, each enough for a move
+    Canadian(Duration, Duration, usize), // main time, then the given time for every so many stones
+}
+
+impl TimeControl {
+    /// Returns how long a move can take with the time already used, sharing any main time left between the moves left
+    ///
+    /// None means the clock doesn't limit the move, and the lag margin is kept back from any time that does
+    pub fn get_move_time(&self, used: Duration, moves_left: usize) -> Option<Duration> {
+        let moves_left = moves_left.max(1) as u32;
+        let time = match *self {
+            TimeControl::None => return None,
+            TimeControl::Absolute(main) => main.saturating_sub(used) / moves_left,
+            TimeControl::ByoYomi(main, period, _) => main.saturating_sub(used) / moves_left + period,
+            TimeControl::Canadian(main, period, stones) => main.saturating_sub(used) / moves_left + period / stones.max(1) as u32,
+        };
+        Some(time.saturating_sub(LAG_MARGIN))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchConfig {
+    pub max_time: Duration, // how long to search for each move
+    pub max_iterations: usize, // how many simulations to run for each move
+    pub max_nodes: usize, // how big the tree can grow before leaves stop being expanded, as each node holds a whole board
+    pub threads: usize, // how many simulations to run at once
+    pub parallelism: Parallelism,
+    pub exploration: f64, // how much UCT favours less visited moves over ones that are winning
+    pub rave_equivalence: f64, // how many visits a move needs before its own win rate counts as much as its AMAF one (0 turns RAVE off)
+    pub fpu: Fpu,
+    pub selection: Selection,
+    pub resign_threshold: f64, // the root win rate the bot resigns below, 0 (the default) never resigns
+    pub resign_moves: usize, // how many of its moves in a row the win rate has to stay below the threshold
+    pub seed: Option<u64>, // seeds the random playouts so a search on one thread can be repeated, otherwise they are different every time
+}
+
+impl SearchConfig {
+    pub fn new() -> Self {
+        SearchConfig {
+            max_time: Duration::from_millis(5000),
+            max_iterations: 25000,
+            max_nodes: 300_000, // roughly a gigabyte
+            threads: 2,
+            parallelism: Parallelism::Tree,
+            exploration: 0.3, // RAVE does most of the exploring
+            rave_equivalence: 1000.0,
+            fpu: Fpu::Infinite,
+            selection: Selection::MostVisits,
+            resign_threshold: 0.0,
+            resign_moves: 3,
+            seed: None,
+        }
+    }
+
+    /// Sets a parameter from its name and a value as text, as given on the command line or over gtp
+    ///
+    /// the names are time (in seconds), iterations, nodes, threads, parallel, exploration, rave, fpu, selection,
+    /// resign, resign-moves and seed
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), ConfigErrors> {
+        let invalid = || ConfigErrors::InvalidValue(name.to_string(), value.to_string());
+
+        match name {
+            "time" => {
+                let seconds: f32 = value.parse().map_err(|_| invalid())?;
+                self.max_time = Duration::try_from_secs_f32(seconds).map_err(|_| invalid())?;
+            }
+            "iterations" => self.max_iterations = value.parse().map_err(|_| invalid())?,
+            "nodes" => self.max_nodes = value.parse().map_err(|_| invalid())?,
+            "threads" => {
+                self.threads = value.parse().map_err(|_| invalid())?;
+                if self.threads == 0 {
+                    return Err(invalid());
+                }
+            }
+            "parallel" => self.parallelism = Parallelism::from_name(value).ok_or_else(invalid)?,
+            "exploration" => self.exploration = value.parse().map_err(|_| invalid())?,
+            "rave" => self.rave_equivalence = value.parse().map_err(|_| invalid())?,
+            "fpu" => self.fpu = Fpu::from_name(value).ok_or_else(invalid)?,
+            "selection" => self.selection = Selection::from_name(value).ok_or_else(invalid)?,
+            "resign" => self.resign_threshold = value.parse().map_err(|_| invalid())?,
+            "resign-moves" => self.resign_moves = value.parse().map_err(|_| invalid())?,
+            "seed" => self.seed = if value == "none" { None } else { Some(value.parse().map_err(|_| invalid())?) },
+            _ => return Err(ConfigErrors::UnknownParameter(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig::new()
+    }
+}
+
+impl std::fmt::Display for SearchConfig {
+    /// lists every parameter by the name it is set with
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "time {}", self.max_time.as_secs_f32())?;
+        writeln!(f, "iterations {}", self.max_iterations)?;
+        writeln!(f, "nodes {}", self.max_nodes)?;
+        writeln!(f, "threads {}", self.threads)?;
+        writeln!(f, "parallel {}", self.parallelism.get_name())?;
+        writeln!(f, "exploration {}", self.exploration)?;
+        writeln!(f, "rave {}", self.rave_equivalence)?;
+        writeln!(f, "fpu {}", self.fpu.get_name())?;
+        writeln!(f, "selection {}", self.selection.get_name())?;
+        writeln!(f, "resign {}", self.resign_threshold)?;
+        writeln!(f, "resign-moves {}", self.resign_moves)?;
+        write!(f, "seed {}", self.seed.map_or(String::from("none"), |seed| seed.to_string()))
+    }
+}
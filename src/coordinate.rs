@@ -2,70 +2,84 @@
 
 use std::hash::Hash;
 
-use crate::BOARD_SIZE;
-
 
 pub type position = (usize, usize); // (x, y)
 pub type index = usize; // index of the 1d vector
 
 #[derive(Clone, Copy, Debug)]
 pub enum Coordinate { // to make it easier to handle the position of the board and transfer between the two formats
-    Position(position),
-    Index(index),
+    Position(position, usize), // the second value is the size of the board the coordinate belongs to
+    Index(index, usize),
 }
 
 impl Coordinate {
     /// convert position to index, or index to position
-    pub fn into(&self) -> Coordinate {
+    pub fn into(self) -> Coordinate {
         match self {
-            Coordinate::Index(value) => {
+            Coordinate::Index(value, size) => {
                 // return position
-               Coordinate::Position((value / BOARD_SIZE, value % BOARD_SIZE))
+               Coordinate::Position((value / size, value % size), size)
             }
-            Coordinate::Position((x, y)) => {
+            Coordinate::Position((x, y), size) => {
                 // return index
-                Coordinate::Index(x * BOARD_SIZE + y)
+                Coordinate::Index(x * size + y, size)
             }
         }
     }
 
-    pub fn into_index(&self) -> Coordinate {
+    pub fn into_index(self) -> Coordinate {
         match self {
-            Coordinate::Index(_value) => {*self},
-            Coordinate::Position((_x, _y)) => {self.into()}
+            Coordinate::Index(_value, _) => {self},
+            Coordinate::Position((_x, _y), _) => {self.into()}
         }
     }
 
-    pub fn into_position(&self) -> Coordinate {
+    pub fn into_position(self) -> Coordinate {
         match self {
-            Coordinate::Index(_value) => {self.into()},
-            Coordinate::Position((_x, _y)) => {*self}
+            Coordinate::Index(_value, _) => {self.into()},
+            Coordinate::Position((_x, _y), _) => {self}
         }
     }
 
     /// returns an index if it is inbounds
-    pub fn _new_index(index: usize) -> Option<Coordinate> {
+    pub fn _new_index(index: usize, size: usize) -> Option<Coordinate> {
 
-        if index < (BOARD_SIZE * BOARD_SIZE) {
-           return Some(Coordinate::Index(index))
+        if index < (size * size) {
+           return Some(Coordinate::Index(index, size))
         }
-        return None
+        None
     }
 
     /// returns a Position if it is inbounds
-    pub fn _new_position(position: position) -> Option<Coordinate> {
-        if position.0 < BOARD_SIZE && position.1 < BOARD_SIZE {
-            return Some(Coordinate::Position(position))
+    pub fn _new_position(position: position, size: usize) -> Option<Coordinate> {
+        if position.0 < size && position.1 < size {
+            return Some(Coordinate::Position(position, size))
+        }
+        None
+    }
+
+    /// returns the size of the board this coordinate belongs to
+    pub fn get_size(&self) -> usize {
+        match self {
+            Coordinate::Index(_, size) => *size,
+            Coordinate::Position(_, size) => *size,
+        }
+    }
+
+    /// returns true if the coordinate lies on its board
+    pub fn in_bounds(&self) -> bool {
+        match *self {
+            Coordinate::Index(value, size) => value < size * size,
+            Coordinate::Position((x, y), size) => x < size && y < size,
         }
-        return None
     }
 
     /// returns the Index of the Coordinate
     pub fn get_index(&self) -> index {
         let new = self.into_index();
         match new {
-            Coordinate::Index(value) => {value},
-            Coordinate::Position(_) => 0,
+            Coordinate::Index(value, _) => {value},
+            Coordinate::Position(_, _) => 0,
         }
     }
 
@@ -73,16 +87,16 @@ impl Coordinate {
     pub fn get_position(&self) -> position {
         let new = self.into_position();
         match new  {
-            Coordinate::Index(_) => {(0, 0)},
-            Coordinate::Position(value) => {value},
+            Coordinate::Index(_, _) => {(0, 0)},
+            Coordinate::Position(value, _) => {value},
         }
     }
 }
 
-// position and index are equivalent if they point to the same location in a 1d vector
+// position and index are equivalent if they point to the same location in a 1d vector of the same board size
 impl PartialEq for Coordinate {
     fn eq(&self, other: &Self) -> bool {
-        return self.get_index() == other.get_index()
+        self.get_size() == other.get_size() && self.get_index() == other.get_index()
     }
 }
 
@@ -91,6 +105,7 @@ impl Eq for Coordinate {}
 impl Hash for Coordinate {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.get_index().hash(state);
+        self.get_size().hash(state);
     }
 }
 
@@ -7,7 +7,71 @@ pub enum TurnErrors { // errors regarding making moves
     OutofBounds,
 }
 
+#[derive(Debug)]
+pub enum BoardErrors { // errors regarding creating boards
+    InvalidSize(usize), // boards have to be between 1 and MAX_BOARD_SIZE points wide
+}
+
+impl std::fmt::Display for BoardErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoardErrors::InvalidSize(size) => write!(f, "unsupported board size {}", size),
+        }
+    }
+}
+
 pub enum TreeErrors { // errors regarding tree navigation
     BelowZero,
     AboveMax,
-}
\ No newline at end of file
+    NoVariation,
+}
+#[derive(Debug)]
+pub enum HandicapErrors { // errors regarding placing handicap stones
+    GameStarted, // handicap can only be placed on an empty board
+    InvalidNumber, // too few or too many stones for the board
+    IllegalPlacement(TurnErrors), // a stone couldn't be placed, ie the same point was given twice
+}
+
+impl std::fmt::Display for HandicapErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandicapErrors::GameStarted => write!(f, "board not empty"),
+            HandicapErrors::InvalidNumber => write!(f, "invalid number of stones"),
+            HandicapErrors::IllegalPlacement(error) => write!(f, "bad vertex list ({:?})", error),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigErrors { // errors regarding the parameters of the search
+    UnknownParameter(String),
+    InvalidValue(String, String), // the parameter and the value it was given
+}
+
+impl std::fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigErrors::UnknownParameter(name) => write!(f, "unknown parameter '{}'", name),
+            ConfigErrors::InvalidValue(name, value) => write!(f, "invalid value '{}' for {}", value, name),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SgfErrors { // errors regarding reading sgf files
+    InvalidSyntax(usize), // the character position the parser failed at
+    UnsupportedGame, // not a game of go or an unsupported board size
+    InvalidCoordinate(String),
+    IllegalMove(TurnErrors), // a move in the file was not legal on our board
+}
+
+impl std::fmt::Display for SgfErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SgfErrors::InvalidSyntax(position) => write!(f, "invalid syntax at character {}", position),
+            SgfErrors::UnsupportedGame => write!(f, "not a supported game of go"),
+            SgfErrors::InvalidCoordinate(point) => write!(f, "invalid coordinate [{}]", point),
+            SgfErrors::IllegalMove(error) => write!(f, "illegal move ({:?})", error),
+        }
+    }
+}
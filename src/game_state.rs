@@ -1,49 +1,127 @@
 use std::cell::RefCell;
 use std::collections::HashSet;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
-use rand::Rng;
-use rand::rngs::ThreadRng;
-use rand::seq::SliceRandom;
-use rayon::iter::{IntoParallelRefIterator, IntoParallelIterator};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rayon::iter::IntoParallelIterator;
 use rayon::prelude::ParallelIterator;
 
-use crate::colour::Outcome;
-use crate::group_state::GroupState;
+use crate::colour::{Outcome, WIN_POINTS};
+use crate::config::{Fpu, Parallelism, SearchConfig, Selection};
+use crate::fails::{BoardErrors, HandicapErrors, SgfErrors};
+use crate::handicap;
+use crate::playout::PlayoutBoard;
+use crate::sgf;
+use crate::rules::Rules;
+use crate::scoring::ScoreBreakdown;
 use crate::{board_state::BoardState, colour::Colour, tree::GameTree, coordinate::Coordinate, fails::TurnErrors, turn::Turn};
 
-pub const AUTO_PLAY: bool = false; // play against the MCTS
-pub const _SELF_PLAY: bool = false; // play against itself
-pub const _WEAK_PLAY: bool = false; // have the MCTS play against a random opponent
-
-pub const KOMI: f32 = 0.5;
+const OWNERSHIP_PLAYOUTS: usize = 64; // how many playouts are used to decide which stones are dead
+const VIRTUAL_LOSS: usize = 1; // the losses added to each node on a path while it is being simulated
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(10); // how often progress is reported while the threads search
+const MIN_ITERATIONS: usize = 16; // the time limit can't stop a search before it has expanded the root and tried a few moves
+const PV_MIN_VISITS: usize = 2; // principal variations end before replies that have only been tried once
+const LCB_Z: f64 = 1.96; // how many standard deviations below the win rate the lower confidence bound is (95%)
 
+#[derive(Clone)]
 pub struct GameState {
     pub board_state: BoardState,
     pub turn: Colour,
     pub game_tree: GameTree,
-    rng: RefCell<ThreadRng>,
+    rng: RefCell<StdRng>, // for the playouts that propose dead stones
     pub size: usize,
-    mcts: Arc<Mutex<MonteCarloSearch>>, // for the persistent mcts
+    pub dead_stones: HashSet<Coordinate>, // stones marked as dead once the game is over
 }
 
 impl GameState {
-    pub fn new(board_size: usize) -> Self {
-        let mcts = Arc::new(Mutex::new(MonteCarloSearch::new(BoardState::new(board_size), Colour::Black)));
-        let mut search = mcts.lock().unwrap();
-        search.expand(0);
+    /// Creates a game on an empty board, the size has to be between 1 and MAX_BOARD_SIZE
+    pub fn new(board_size: usize) -> Result<Self, BoardErrors> {
+        Ok(GameState {
+            board_state: BoardState::new(board_size)?,
+            turn: Colour::Black,
+            game_tree: GameTree::new(board_size)?,
+            rng: RefCell::new(StdRng::from_entropy()),
+            size: board_size,
+            dead_stones: HashSet::new(),
+        })
+    }
 
-        println!("{}", search.nodes.len());
+    /// Creates a game from an existing tree (ie one loaded from an sgf file), continuing from its latest position
+    pub fn from_game_tree(game_tree: GameTree) -> Self {
+        let mut game = GameState::new(game_tree.get_root().board.size).expect("the tree already has a board of this size");
 
+        game.board_state = game_tree.get_current_board().clone();
+        game.turn = game_tree.get_board().0;
+        game.game_tree = game_tree;
+        game.update_dead_stones();
+        game
+    }
 
-        GameState {
-            board_state: BoardState::new(board_size),
-            turn: Colour::Black,
-            game_tree: GameTree::new(board_size),
-            rng: RefCell::new(rand::thread_rng()),
-            size: board_size,
-            mcts: mcts.clone(),
+    /// Reads a game from the text of an sgf file
+    pub fn from_sgf(text: &str) -> Result<GameState, SgfErrors> {
+        Ok(GameState::from_game_tree(sgf::load_game(text)?))
+    }
+
+    /// Places the given handicap stones for black on the empty board, after which white plays first
+    pub fn place_handicap(&mut self, points: &[Coordinate]) -> Result<(), HandicapErrors> {
+        let empty = self.board_state.get_grid().iter().all(|&point| point == Colour::Empty);
+        if !self.game_tree.get_root().children.is_empty() || !empty {
+            return Err(HandicapErrors::GameStarted);
+        }
+        if points.len() < handicap::MIN_HANDICAP || points.len() >= self.size * self.size {
+            return Err(HandicapErrors::InvalidNumber);
+        }
+
+        // the stones are set up rather than played, so they aren't part of the history and white is to play
+        let stones: Vec<(Coordinate, Colour)> = points.iter().map(|&point| (point, Colour::Black)).collect();
+        let board = self.board_state.setup_stones(&stones, Colour::White).map_err(HandicapErrors::IllegalPlacement)?;
+
+        self.game_tree.set_root_board(board.clone());
+        self.game_tree.set_first_colour(Colour::White);
+        self.game_tree.info.handicap = points.len();
+        self.board_state = board;
+        self.turn = Colour::White;
+        Ok(())
+    }
+
+    /// places the standard handicap stones for the board, returning where they were placed
+    pub fn place_fixed_handicap(&mut self, stones: usize) -> Result<Vec<Coordinate>, HandicapErrors> {
+        let points = handicap::fixed_handicap(self.size, stones).ok_or(HandicapErrors::InvalidNumber)?;
+        self.place_handicap(&points)?;
+        Ok(points)
+    }
+
+    /// chooses where to place the handicap stones, returning where they were placed
+    pub fn place_free_handicap(&mut self, stones: usize) -> Result<Vec<Coordinate>, HandicapErrors> {
+        let points = handicap::free_handicap(self.size, stones).ok_or(HandicapErrors::InvalidNumber)?;
+        self.place_handicap(&points)?;
+        Ok(points)
+    }
+
+    /// seeds the playouts used to propose dead stones so they are the same every time
+    pub fn set_seed(&mut self, seed: u64) {
+        *self.rng.borrow_mut() = StdRng::seed_from_u64(seed);
+    }
+
+    /// returns the rules the game is played under
+    pub fn get_rules(&self) -> Rules {
+        self.board_state.rules
+    }
+
+    /// changes the rules of the game
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.board_state.rules = rules;
+        self.game_tree.set_rules(rules);
+    }
+
+    /// Writes the game as sgf text, including the result if the game is over
+    pub fn export_sgf(&mut self) -> String {
+        if let Some(result) = self.get_result() {
+            self.game_tree.info.result = Some(result);
         }
+        sgf::save_game(&self.game_tree)
     }
 
     /// Swap the colour
@@ -54,10 +132,6 @@ impl GameState {
     /// Attempts to play a move at the specified position and returns whether it was successful
     pub fn play_move(&mut self, coordinate: Coordinate) -> bool {
         let new_position = self.board_state.add_stone(coordinate, self.turn);
-        if !self.game_tree.up_to_date() {
-            println!("Please reach the current position before playing a move");
-            return false;
-        }
 
         match new_position {
             Ok(state) => {
@@ -67,62 +141,69 @@ impl GameState {
             }
 
             Err(error) =>{
-                let turn_number = self.game_tree.get_length();
+                let turn_number = self.game_tree.get_pointer() + 1;
                 let turn_colour: String = self.turn.get_string();
                 match error {
-                    TurnErrors::AlreadyPlaced => println!("Error on Move {}: {} Stone already in the position at {:?}", turn_number, turn_colour, coordinate),
-                    TurnErrors::Ko => println!("Error on Move {}: {} can't place due to Ko", turn_number, turn_colour),
-                    TurnErrors::Suicide => println!("Error on Move {}: {} can't place due to suicide at {:?}", turn_number, turn_colour, coordinate),
-                    TurnErrors::OutofBounds => println!("Error on Move {}: {} can't place due to out of bounds at {:?}", turn_number, turn_colour, coordinate),
+                    TurnErrors::AlreadyPlaced => eprintln!("Error on Move {}: {} Stone already in the position at {:?}", turn_number, turn_colour, coordinate),
+                    TurnErrors::Ko => eprintln!("Error on Move {}: {} can't place due to Ko", turn_number, turn_colour),
+                    TurnErrors::Suicide => eprintln!("Error on Move {}: {} can't place due to suicide at {:?}", turn_number, turn_colour, coordinate),
+                    TurnErrors::OutofBounds => eprintln!("Error on Move {}: {} can't place due to out of bounds at {:?}", turn_number, turn_colour, coordinate),
                 }
 
                 return false;
             }
         }
 
-        /* TODO:
-                if AUTO_PLAY { // handle mcts tree pruning
-            let mut mcts = self.mcts.lock().unwrap();
-            mcts.expand(0); 
-
-            let root_node = &mcts.nodes[mcts.root];
-
-            let played_move_node_index = root_node.children.iter()
-                .find(|&&child_index| mcts.nodes[child_index].game_move == Some(coordinate))
-                .cloned()
-                .expect("Didn't find the move");
-            mcts.prune(played_move_node_index);
-        }
-        */
-
-        return true;
+        true
     }
 
     /// handles all Turn Enum Arms: Move, Pass and Resign
-    pub fn play_turn(&mut self, turn: Turn) {
+    /// 
+    /// returns false if the turn couldn't be played
+    pub fn play_turn(&mut self, turn: Turn) -> bool {
         match turn {
             Turn::Move(coordinate) => {
-                if self.play_move(coordinate) {
-                    self.game_tree.add_move(turn, self.board_state.clone());
+                if !self.play_move(coordinate) {
+                    return false;
                 }
-
-                if AUTO_PLAY {
-                    if self.turn == Colour::White {
-                        self.auto_move();
-                    }
-                }
-
-                
+                self.game_tree.add_move(turn, self.turn.swap_turn(), self.board_state.clone());
+                self.update_dead_stones();
             },
             Turn::Pass => {
-                println!("{} has Passed.", self.turn.get_string());
+                eprintln!("{} has Passed.", self.turn.get_string());
+                self.board_state = self.board_state.pass(self.turn);
+                self.game_tree.add_move(turn, self.turn, self.board_state.clone());
                 self.swap_turn();
-                self.game_tree.add_move(turn, self.board_state.clone());
+                self.update_dead_stones(); // two passes starts the scoring phase
             },
             Turn::Resign => {
-                self.game_tree.add_move(turn, self.board_state.clone())
+                self.game_tree.add_move(turn, self.turn, self.board_state.clone());
+                self.update_dead_stones();
             }
         }
+        true
+    }
+
+    /// takes back the latest turn, returning false if there is nothing to take back
+    pub fn undo(&mut self) -> bool {
+        let colour = self.game_tree.get_last_colour();
+
+        if self.game_tree.undo().is_err() {
+            return false;
+        }
+
+        self.board_state = self.game_tree.get_current_board().clone();
+        self.turn = colour;
+        self.update_dead_stones();
+        true
+    }
+
+    /// updates the board and turn to match the position being looked at in the game tree
+    fn sync_with_tree(&mut self) {
+        let (colour, board) = self.game_tree.get_board();
+        self.board_state = board;
+        self.turn = colour;
+        self.update_dead_stones();
     }
 
     /// get all possible moves for the current board state and colour through brute force
@@ -130,8 +211,8 @@ impl GameState {
         let mut possible_moves = Vec::new();
         let current_state = self.board_state.clone();
 
-        (0..self.size * self.size).into_iter().for_each(|i| {
-            let coordinate = Coordinate::Index(i);
+        (0..self.size * self.size).for_each(|i| {
+            let coordinate = Coordinate::Index(i, self.size);
             let new_position = current_state.add_stone(coordinate, colour);
 
             if new_position.is_ok() {
@@ -145,7 +226,7 @@ impl GameState {
     /// Get all possible moves for a given board state and colour using brute force and parallelization
     pub fn get_all_possible_moves_for_board(board: &BoardState, colour: Colour) -> Vec<Coordinate> {
         (0..board.size * board.size).into_par_iter().filter_map(|i| {
-            let coordinate = Coordinate::Index(i);
+            let coordinate = Coordinate::Index(i, board.size);
             let new_position = board.add_stone(coordinate, colour);
 
             if new_position.is_ok() {
@@ -156,511 +237,835 @@ impl GameState {
         }).collect()
     }
 
-    /// Chooses a random coordinate from the possible coordinate
-    pub fn play_random_move(&self, possible_moves: &[Coordinate]) -> Option<Coordinate> {
-        if possible_moves.is_empty() {
-           return None;
-        }
-
-        let mut rng = self.rng.borrow_mut();
-        let index: usize = rng.gen_range(0..possible_moves.len()); // chosen position
-        Some(possible_moves[index])
-    }
-
     /// Returns true if either black or white have possible moves to play
     pub fn _moves_to_play(&self) -> bool {
-        let black_moves = self.get_all_possible_moves(Colour::Black).len() > 0;
-        let white_moves = self.get_all_possible_moves(Colour::White).len() > 0;
+        let black_moves = !self.get_all_possible_moves(Colour::Black).is_empty();
+        let white_moves = !self.get_all_possible_moves(Colour::White).is_empty();
 
         black_moves || white_moves
     }
 
     pub fn count_possible_moves(&self) {
-        println!("{}", self.get_all_possible_moves(self.turn).len());
+        eprintln!("{}", self.get_all_possible_moves(self.turn).len());
     }
 
-    pub fn random_completed_game(&mut self) {
-        println!("Playing random game (but like better than the other one)");
-
-        while !self.check_end() {
-            if self.board_state.check_all_important_points_played() {
-                // all important points are played for the given turn
-                self.play_turn(Turn::Pass);
-            } 
-
-            // else we need to play a random move
-
-            let possible_moves = self.get_all_possible_moves(self.turn);
-
-            if possible_moves.len() < 3 {
-                if possible_moves.is_empty() {
-                    self.play_turn(Turn::Resign);
-                } else {
-                    self.play_turn(Turn::Pass);
-                }
-            }
+    /// Plays a random move if possible
+    /// clamps the coordinate to be within the max size of the board
+    pub fn clamp_coordinate(&self, x: usize, y: usize) -> Coordinate {
+        let new_x = x.min(self.size - 1);
+        let new_y = y.min(self.size - 1);
+    
+        Coordinate::Position((new_x, new_y), self.size)
+    }
 
-            let random_move = self.play_random_move(&possible_moves);
+    /// return true if the game is over by resignation or passing
+    pub fn check_end(&self) -> bool {
+        self.game_tree.check_end()
+    }
 
-            match random_move {
-                Some(m) => self.play_turn(Turn::Move(m)),
-                None => self.play_turn(Turn::Pass),
-            }
+    /// returns the result of a finished game in sgf form ie "B+R", "W+3.5" or "0" for a draw
+    pub fn get_result(&self) -> Option<String> {
+        if !self.check_end() {
+            return None;
+        }
 
+        if let Some((Turn::Resign, colour)) = self.game_tree.get_turns().last() {
+            let winner = if *colour == Colour::Black { "W" } else { "B" };
+            return Some(format!("{}+R", winner));
         }
 
-        println!("Game over - and should be like fairly reasonable.");
-        self.calculate_total_completed_score();
+        Some(self.score().get_result())
     }
 
-
-    /// return true if the only moves to play are within a player's own territory
-    /// also return false if there are dame points still left
-    pub fn check_useful_points_played(board: &BoardState, colour: Colour) -> bool {
-        let grid = board.get_grid();
-        
-        let all_possible_moves = GameState::get_all_possible_moves_for_board(board, colour); // generate all possible moves for this colour
-        let territory = board.get_territory(&grid, colour); // a vector of empty territoy groups
-        let coords: HashSet<Coordinate> = GroupState::combine_groups(&territory); // a hashset of coordinates that are in the territory
-    
-        let useful_moves = all_possible_moves.iter().filter(|&move_to_play| coords.contains(move_to_play)).count(); // the number of moves that are in the territory
-    
-        //println!("Useful Moves: {} | All possible moves: {}", useful_moves, all_possible_moves.len());
-    
-        return useful_moves == all_possible_moves.len();
+    /// moves the game tree pointer forward one (called when mousewheel is scrolled down)
+    pub fn jump_forward(&mut self) {
+        if self.game_tree.move_forward().is_ok() {
+            self.sync_with_tree();
+        }
     }
 
-
-    /// Plays a random move if possible
-    pub fn auto_move(&mut self) {
-        if self.game_tree.get_length() < 30 {
-            // generate a random move instead
-            //let possible_moves = self.get_all_possible_moves(self.turn);
-            //self.play_turn(Turn::Move(possible_moves.last().unwrap().clone())); 
-            //self.play_turn(Turn::Move(self.play_random_move(&possible_moves).unwrap()));
-            //return;
-        }
-        match self.decide_next_move(self.turn) {
-            Some(coordinate) => self.play_turn(Turn::Move(coordinate)),
-            None => self.play_turn(Turn::Pass),
+    /// moves the game tree pointer back one (called when mousewheel is scrolled up)
+    pub fn jump_back(&mut self) {
+        if self.game_tree.move_back().is_ok() {
+            self.sync_with_tree();
         }
     }
 
-    pub fn weak_play(&mut self) {
-        // Black is Random, White is MCTS
-        println!("Random vs MCTS starting");
-        let mut counter = 0;
-        loop {
-            if self.board_state.check_all_important_points_played() {
-                // all important points are played for the given turn
-                self.play_turn(Turn::Pass);
-            } 
-
-            // else we need to play a random move
+    /// returns true if the game ended by passing and the dead stones are being decided
+    pub fn in_scoring_phase(&self) -> bool {
+        self.check_end() && self.game_tree.get_current_node().turn == Turn::Pass
+    }
 
-            if self.turn == Colour::Black {
-                let possible_moves = self.get_all_possible_moves(self.turn);
-                let random_move = self.play_random_move(&possible_moves);
+    /// clears the dead stones, and proposes new ones if the game has just ended
+    fn update_dead_stones(&mut self) {
+        self.dead_stones.clear();
+        if self.in_scoring_phase() {
+            self.dead_stones = self.propose_dead_stones();
+        }
+    }
 
-                match random_move {
-                    Some(m) => self.play_turn(Turn::Move(m)),
-                    None => self.play_turn(Turn::Pass),
-                }
-            } else {
-                self.auto_move();
-            }
+    /// Proposes which stones are dead by playing out the position randomly many times
+    /// 
+    /// a group is dead if its points end up belonging to the opponent in most of the playouts
+    pub fn propose_dead_stones(&self) -> HashSet<Coordinate> {
+        let board = &self.board_state;
+        let colour = self.turn;
+        let seed: u64 = self.rng.borrow_mut().gen(); // so a seeded game proposes the same stones
+
+        let owners: Vec<Vec<Colour>> = (0..OWNERSHIP_PLAYOUTS).into_par_iter().map(|playout| {
+            let mut rng = StdRng::seed_from_u64(seed.wrapping_add(playout as u64));
+            let mut playout = PlayoutBoard::from_board(board);
+            playout.play_random_game(colour, 0, &mut rng);
+            playout.get_ownership()
+        }).collect();
+
+        board.get_current_groups()
+            .into_iter()
+            .filter(|group| {
+                let opponent = group.colour.swap_turn();
+                let lost: usize = group.get_points().iter()
+                    .map(|point| owners.iter().filter(|owner| owner[point.get_index()] == opponent).count())
+                    .sum();
+                lost * 2 > group.get_points().len() * OWNERSHIP_PLAYOUTS
+            })
+            .flat_map(|group| group.get_positions())
+            .collect()
+    }
 
-            if self.check_end() {
-                break;
-            }
-            counter += 1;
+    /// marks the group at the given coordinate as dead, or as alive if it was already dead
+    pub fn toggle_dead_group(&mut self, coordinate: Coordinate) {
+        let Some(group) = self.board_state.find_group(coordinate) else {
+            return;
+        };
 
-            if counter > 200 {
-                break;
+        if self.dead_stones.contains(&coordinate) {
+            for point in group.get_points() {
+                self.dead_stones.remove(point);
             }
+        } else {
+            self.dead_stones.extend(group.get_points());
         }
-
-        println!("Done after {} moves", counter);
     }
 
-    /// clamps the coordinate to be within the max size of the board
-    pub fn clamp_coordinate(&self, x: usize, y: usize) -> Coordinate {
-        let mut new_x = x;
-        let mut new_y = y;
-    
-        if new_x > self.size {
-            new_x = self.size;
-        }
-    
-        if new_y > self.size {
-            new_y = self.size;
-        }
-    
-        Coordinate::Position((new_x, new_y))
+    /// returns the board used for scoring, which is the current board without the dead stones
+    pub fn get_scoring_board(&self) -> BoardState {
+        self.board_state.remove_stones(&self.dead_stones)
     }
 
-    /// return true if the game is over by resignation or passing
-    pub fn check_end(&self) -> bool {
-        self.game_tree.check_end()
+    /// agrees on the dead stones and records the final result of the game
+    pub fn accept_score(&mut self) {
+        self.game_tree.info.result = self.get_result();
     }
 
-    /// moves the game tree pointer forward one (called when mousewheel is scrolled down)
-    pub fn jump_forward(&mut self) {
-        if self.game_tree.up_to_date() {
-            return; // no need to change the board position
+    /// switches to the next or previous variation of the current move
+    pub fn switch_variation(&mut self, forward: bool) {
+        if self.game_tree.change_variation(forward).is_ok() {
+            self.sync_with_tree();
         }
-
-        let _ = self.game_tree.move_forward();
     }
 
-    /// moves the game tree pointer forward one (called when mousewheel is scrolled down)
-    pub fn jump_back(&mut self) {
-        if self.game_tree.get_pointer() == 0 {
-            return; // no need to change the board position
+    /// counts the score of the game under the chosen rule, with the stones marked as dead taken off the board
+    pub fn score(&self) -> ScoreBreakdown {
+        let mut passes = [0, 0]; // how many times black and white passed (for AGA pass stones)
+        for (turn, colour) in self.game_tree.get_turns() {
+            if turn == Turn::Pass && colour != Colour::Empty {
+                passes[colour.into_usize() - 1] += 1;
+            }
         }
 
-        let _ = self.game_tree.move_back();
+        ScoreBreakdown::count(&self.get_scoring_board(), passes)
     }
 
-    /// calculate the score of the game assuming there are no dead stones
+    /// calculate the score of the game with the stones marked as dead taken off the board
     pub fn calculate_total_completed_score(&self) -> (Colour, f32) {
-        // ie this just finds empty spots, assigns them to a big group
-        // matches the groups to a colour and then sums up the empty spots
-        // chinese scoring is empty spots + number of stones on the board
-
-        let grid = self.board_state.get_grid(); // the board state
-        
-        let (black_stone_count, white_stone_count): (usize, usize) = grid.iter()
-            .map(|&colour| match colour {
-                Colour::Black => (1, 0),
-                Colour::White => (0, 1),
-                Colour::Empty => (0, 0),
-            })
-        .fold((0, 0), |acc, counts| (acc.0 + counts.0, acc.1 + counts.1));
+        self.score().get_winner()
+    }
+}
 
-        // Count the number of empty intersections surrounded by each colour
-        let (black_area, white_area) = self.board_state.get_colour_territory(&grid);
 
-        // Chinese scoring: empty spots + number of stones on the board
-        let black_score = (black_area + black_stone_count) as f32;
-        let white_score = (white_area + white_stone_count) as f32 + KOMI; // komi is always some 0.5 value
+pub struct MonteCarloNode { // maybe?
+    pub state: Arc<BoardState>, // the actual position of the board
+    pub parent: Option<usize>,
+    pub children: Vec<usize>, // A list of the children's ids
+    pub wins: AtomicUsize, // how many wins this node leads to, in WIN_POINTS so draws can count as half
+    pub visits: AtomicUsize, // how many times has this node been visited
+    pub amaf_wins: AtomicUsize, // wins (in WIN_POINTS) of the playouts where the move was played later by the same player (all moves as first)
+    pub amaf_visits: AtomicUsize, // playouts where the move was played later by the same player
+    pub virtual_loss: AtomicUsize, // visits still being simulated by other threads, which count as losses until they finish
+    pub colour: Colour, // Turn to play
+    pub id: usize, // the index in the node list
+    pub game_move: Option<Turn>, // the move that led to this node (None for the root)
+    pub passes: usize, // how many passes in a row led to this node, two passes ends the game
+}
 
-        // Return the total scores for both colours
-        if black_score > white_score {
-            (Colour::Black, black_score - white_score)
-        } else {
-            (Colour::White, white_score - black_score)
+impl MonteCarloNode {
+    /// creates a node that hasn't been visited
+    pub fn new(state: Arc<BoardState>, parent: Option<usize>, colour: Colour, id: usize, game_move: Option<Turn>, passes: usize) -> Self {
+        MonteCarloNode {
+            state,
+            parent,
+            children: Vec::new(),
+            wins: AtomicUsize::new(0),
+            visits: AtomicUsize::new(0),
+            amaf_wins: AtomicUsize::new(0),
+            amaf_visits: AtomicUsize::new(0),
+            virtual_loss: AtomicUsize::new(0),
+            colour,
+            id,
+            game_move,
+            passes,
         }
     }
 
-    /// Let the MCTS know if the game is over
-    pub fn is_game_over(board: &BoardState) -> bool {
-        /* 
-        // returns true if therre are still points that dont belong to either territory
-        //let first = board.check_all_important_points_played();
-
-        // this function returns true if the only moves to play are within a player's own territory 
-        let black = GameState::check_useful_points_played(board, Colour::Black);
-        let white = GameState::check_useful_points_played(board, Colour::White);
+    /// returns true if the game ended by two passes in a row
+    pub fn is_terminal(&self) -> bool {
+        self.passes >= 2
+    }
 
-        black && white */
-        board.check_all_important_points_played()
+    pub fn get_visits(&self) -> usize {
+        self.visits.load(Ordering::Relaxed)
     }
 
-    /// Let the MCTS know the outcome of the game
-    pub fn determine_outcome(board: &BoardState) -> Outcome {
-        // ie this just finds empty spots, assigns them to a big group
-        // matches the groups to a colour and then sums up the empty spots
-        // chinese scoring is empty spots + number of stones on the board
+    /// returns the share of the finished visits that the player who moved into this node won
+    pub fn get_win_rate(&self) -> f64 {
+        self.wins.load(Ordering::Relaxed) as f64 / (WIN_POINTS * self.get_visits().max(1)) as f64
+    }
+}
 
-        let grid = board.get_grid(); // the board state
-        
-        let (black_stone_count, white_stone_count): (usize, usize) = grid.iter()
-            .map(|&colour| match colour {
-                Colour::Black => (1, 0),
-                Colour::White => (0, 1),
-                Colour::Empty => (0, 0),
-            })
-        .fold((0, 0), |acc, counts| (acc.0 + counts.0, acc.1 + counts.1));
+/// the statistics of a move from the root, which can be added up across the trees of a root parallel search
+#[derive(Clone, Copy, Debug)]
+pub struct MoveStats {
+    pub turn: Turn,
+    pub visits: usize,
+    pub wins: usize, // for the player making the move, in WIN_POINTS
+    pub amaf_visits: usize,
+    pub amaf_wins: usize,
+}
 
-        // Count the number of empty intersections surrounded by each colour
-        let black_area = board.get_surrounded_area(&grid, Colour::Black);
-        let white_area = board.get_surrounded_area(&grid, Colour::White);
+impl MoveStats {
+    /// adds the statistics of the same move from another tree
+    pub fn merge(&mut self, other: &MoveStats) {
+        self.visits += other.visits;
+        self.wins += other.wins;
+        self.amaf_visits += other.amaf_visits;
+        self.amaf_wins += other.amaf_wins;
+    }
 
-        // Chinese scoring: empty spots + number of stones on the board
-        let black_score = (black_area + black_stone_count) as f32;
-        let white_score = (white_area + white_stone_count) as f32 + KOMI;
+    pub fn get_win_rate(&self) -> f64 {
+        self.wins as f64 / (WIN_POINTS * self.visits.max(1)) as f64
+    }
 
-        if black_score > white_score {
-            return Outcome::BlackWin;
-        } else {
-            return Outcome::WhiteWin;
+    /// returns the Wilson lower confidence bound of the win rate, which stays low until a move has been visited enough
+    pub fn get_lower_bound(&self) -> f64 {
+        if self.visits == 0 {
+            return 0.0;
         }
+
+        let n = self.visits as f64;
+        let p = self.get_win_rate();
+        let z2 = LCB_Z * LCB_Z;
+        (p + z2 / (2.0 * n) - LCB_Z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt()) / (1.0 + z2 / n)
     }
-        
-    /// Use the MCTS to return a coordinate to play.
-    /// 
-    /// This is only called if it is determined there should be a move to play
-    /// Passes and resignations are handled elsewhere
-    pub fn decide_next_move(&self, colour: Colour) -> Option<Coordinate> {
-        let mcts = Arc::new(Mutex::new(MonteCarloSearch::new(self.board_state.clone(), colour)));
 
-        // Parameters:
-        let max_time = Duration::from_millis(5000);
-        let max_iterations = 25000;
-        let num_threads = 2;
-        
-        let start = std::time::Instant::now();
-        let iterations = Arc::new(Mutex::new(0));
+    /// returns the all moves as first win rate, which is what rave starts the move from before it has visits of its own
+    pub fn get_prior(&self) -> f64 {
+        self.amaf_wins as f64 / (WIN_POINTS * self.amaf_visits.max(1)) as f64
+    }
+}
+
+/// What the search thinks of a move from the root, for showing why it plays what it plays
+///
+/// there is no policy network, so the prior is the move's all moves as first win rate
+#[derive(Clone, Debug)]
+pub struct MoveAnalysis {
+    pub turn: Turn,
+    pub visits: usize,
+    pub win_rate: f64, // for the player making the move
+    pub prior: f64,
+    pub lower_bound: f64,
+    pub pv: Vec<Turn>, // the principal variation, starting with this move
+}
 
-        println!("Starting MCTS with max_time: {:?}, max_iterations: {}", max_time, max_iterations);
+/// The tree of the MCTS, which any number of threads can search at once
+///
+/// the statistics of each node are atomic so selection and backpropagation only need to read the node list,
+/// which is locked for writing just long enough to add the children of a node
+pub struct MonteCarloSearch {
+    pub nodes: RwLock<Vec<MonteCarloNode>>, // Where each index is the id of the node
+    pub root: usize, // the starting position -> either an empty board or the current board
+    pub helpers: Vec<MonteCarloSearch>, // the other trees of a root parallel search, which follow this one from move to move
+}
 
-        while start.elapsed() < max_time && *iterations.lock().unwrap() < max_iterations {
-            let mcts_clone = mcts.clone();
-            let iterations_clone = iterations.clone();
 
-            // Parallelize the Simulation phase
-            (0..num_threads).into_par_iter().for_each(|_| {
-                let mut mcts_locked = mcts_clone.lock().unwrap();
+impl MonteCarloSearch {
+    pub fn new(board: BoardState, colour: Colour, passes: usize) -> Self {
+        let root_node = MonteCarloNode::new(board.into(), None, colour, 0, None, passes);
 
-                // Selection
-                let leaf_index = mcts_locked.select_leaf(mcts_locked.root);
+        MonteCarloSearch {
+            nodes: RwLock::new(vec![root_node]),
+            root: 0,
+            helpers: Vec::new(),
+        }
+    }
 
-                // Expansion
-                mcts_locked.expand(leaf_index);
+    /// Searches from the given position until the time or iteration limit is reached, or until stop is set
+    /// 
+    /// progress is called with the number of iterations, the time taken and the search itself as it goes,
+    /// and the previous search is reused if it is for the same position. With root parallelism
+    /// each thread searches its own tree and the move is chosen from their statistics added up
+    pub fn search(mcts: &Arc<Mutex<MonteCarloSearch>>, board: &BoardState, colour: Colour, passes: usize, config: &SearchConfig, stop: &AtomicBool, mut progress: impl FnMut(usize, Duration, &MonteCarloSearch)) -> Turn {
+        let mut search = mcts.lock().unwrap(); // held for the whole search, the threads share the tree through its own lock
+
+        // reuse the previous search if it is for this position, otherwise start again
+        if search.matches(board, colour, passes) {
+            if search.get_root_visits() > 0 {
+                eprintln!("Reusing {} visits from the previous search", search.get_root_visits());
+            }
+        } else {
+            *search = MonteCarloSearch::new(board.clone(), colour, passes);
+        }
 
-                // Simulation
-                let outcome = mcts_locked.simulate(leaf_index);
+        // a root parallel search needs a helper tree for every thread after the first
+        let helpers = if config.parallelism == Parallelism::Root { config.threads - 1 } else { 0 };
+        search.helpers.truncate(helpers);
+        for helper in search.helpers.iter_mut().filter(|helper| !helper.matches(board, colour, passes)) {
+            *helper = MonteCarloSearch::new(board.clone(), colour, passes);
+        }
+        while search.helpers.len() < helpers {
+            search.helpers.push(MonteCarloSearch::new(board.clone(), colour, passes));
+        }
 
-                // Backpropagation
-                mcts_locked.backpropagate(leaf_index, outcome);
+        let search = &*search;
+        let trees: Vec<&MonteCarloSearch> = std::iter::once(search).chain(&search.helpers).collect();
 
-                // Increment iterations in a thread-safe way
-                let mut iter_locked = iterations_clone.lock().unwrap();
-                *iter_locked += 1;
+        // Parameters:
+        let max_time = config.max_time;
+        let max_iterations = config.max_iterations;
+        let num_threads = config.threads;
+        
+        // max-robust can search for up to half as long again, until the most visited move has the best win rate
+        let (extra_time, extra_iterations) = match config.selection {
+            Selection::MaxRobust => (max_time / 2, max_iterations / 2),
+            _ => (Duration::ZERO, 0),
+        };
 
-                //println!("Thread {:?} completed!", thread::current().id());
-            });
-            //println!("Completed {} iterations", *iterations.lock().unwrap());
-        }
+        let start = std::time::Instant::now();
+        let iterations = AtomicUsize::new(0);
+        let root_visits = search.get_root_visits();
+
+        eprintln!("Starting MCTS with max_time: {:?}, max_iterations: {}", max_time, max_iterations);
+
+        std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..num_threads).map(|thread| {
+                let iterations = &iterations;
+                let tree = trees[thread % trees.len()];
+
+                scope.spawn(move || {
+                    // each thread has its own random playouts, which are repeatable with a seed when there is only one thread
+                    let mut rng = match config.seed {
+                        Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(root_visits as u64).wrapping_add(thread as u64)),
+                        None => StdRng::from_rng(rand::thread_rng()).unwrap(),
+                    };
+
+                    while (start.elapsed() < max_time + extra_time || iterations.load(Ordering::Relaxed) < MIN_ITERATIONS) && iterations.load(Ordering::Relaxed) < max_iterations + extra_iterations && !stop.load(Ordering::Relaxed) {
+                        let done = iterations.load(Ordering::Relaxed);
+                        let over_budget = (start.elapsed() >= max_time && done >= MIN_ITERATIONS) || done >= max_iterations;
+                        if over_budget && search.is_max_robust() {
+                            break;
+                        }
+
+                        tree.run_iteration(config, &mut rng);
+                        iterations.fetch_add(1, Ordering::Relaxed);
+                    }
+                })
+            }).collect();
 
-        println!("FINAL NUMBER OF ITERATIONS: {} in {:?}", *iterations.lock().unwrap(), start.elapsed());
+            while !threads.iter().all(|thread| thread.is_finished()) {
+                std::thread::sleep(PROGRESS_INTERVAL);
+                progress(iterations.load(Ordering::Relaxed), start.elapsed(), search);
+            }
+        });
 
-        let mcts = mcts.lock().unwrap();
+        eprintln!("FINAL NUMBER OF ITERATIONS: {} in {:?}", iterations.load(Ordering::Relaxed), start.elapsed());
 
         // After the MCTS loop
-        let best_child_index_option = mcts.nodes[mcts.root].children.par_iter()
-    .max_by(|&a, &b| {
-        let win_ratio_a = mcts.nodes[*a].wins as f64 / mcts.nodes[*a].visits as f64;
-        let win_ratio_b = mcts.nodes[*b].wins as f64 / mcts.nodes[*b].visits as f64;
-        win_ratio_a.partial_cmp(&win_ratio_b).unwrap_or(std::cmp::Ordering::Equal)
-    });
+        let best_move_option = Self::choose_move(&search.get_merged_stats(), config.selection);
 
         // Return the best move
-        if let Some(best_child_index) = best_child_index_option {
+        if let Some(best_move) = best_move_option {
             // Return the best move
-            println!("Decided on Move at: {:?} with winrate: {} after {} visits", mcts.nodes[*best_child_index].game_move.unwrap(), mcts.nodes[*best_child_index].wins as f64 / mcts.nodes[*best_child_index].visits as f64, mcts.nodes[*best_child_index].visits);
-            Some(mcts.nodes[*best_child_index].game_move.unwrap())
+            eprintln!("Decided on Move at: {:?} with winrate: {} after {} visits", best_move.turn, best_move.get_win_rate(), best_move.visits);
+            best_move.turn
+        } else if passes < 2 {
+            // stopped before any move was tried, so play the first legal one rather than giving the game away by passing
+            eprintln!("No moves were searched, playing the first legal move");
+            GameState::get_all_possible_moves_for_board(board, colour).first().map_or(Turn::Pass, |&game_move| Turn::Move(game_move))
         } else {
-            None
+            Turn::Pass
         }
     }
-}
 
+    /// Returns the move to play from the statistics of the root's children, None if there are none
+    ///
+    /// ties are broken on win rate (or visits when choosing by win rate), then go to a move over passing and then to the first child
+    fn choose_move(stats: &[MoveStats], selection: Selection) -> Option<MoveStats> {
+        let key = |stats: &MoveStats| match selection {
+            Selection::MostVisits | Selection::MaxRobust => (stats.visits as f64, stats.get_win_rate()),
+            Selection::Lcb => (stats.get_lower_bound(), stats.get_win_rate()),
+            Selection::WinRate => (stats.get_win_rate(), stats.visits as f64),
+        };
 
-pub struct MonteCarloNode { // maybe?
-    pub state: Arc<BoardState>, // the actual position of the board
-    pub parent: Option<usize>,
-    pub children: Vec<usize>, // A list of the children's ids
-    pub wins: usize, // how many wins this node leads to
-    pub visits: usize, // how many times has this node been visited
-    pub colour: Colour, // Turn to play
-    pub id: usize, // the index in the node list
-    pub game_move: Option<Coordinate>, // the move that led to this node
-}
+        stats.iter()
+            .filter(|stats| stats.visits > 0)
+            .rev() // max_by keeps the last of equal elements
+            .max_by(|a, b| {
+                key(a).partial_cmp(&key(b)).unwrap_or(std::cmp::Ordering::Equal)
+                    .then((a.turn != Turn::Pass).cmp(&(b.turn != Turn::Pass)))
+            })
+            .copied()
+    }
 
-pub struct MonteCarloSearch {
-    pub nodes: Vec<MonteCarloNode>, // Where each index is the id of the node
-    pub root: usize, // the starting position -> either an empty board or the current board
-}
+    /// returns true if the most visited move from the root also has the best win rate
+    fn is_max_robust(&self) -> bool {
+        let stats = self.get_merged_stats();
+        let most_visited = Self::choose_move(&stats, Selection::MostVisits).map(|stats| stats.turn);
+        let best_win_rate = Self::choose_move(&stats, Selection::WinRate).map(|stats| stats.turn);
+        most_visited == best_win_rate
+    }
 
+    /// returns the statistics of every move from the root of this tree
+    pub fn get_root_stats(&self) -> Vec<MoveStats> {
+        let nodes = self.nodes.read().unwrap();
+        nodes[self.root].children.iter()
+            .map(|&child| &nodes[child])
+            .map(|node| MoveStats {
+                turn: node.game_move.unwrap(),
+                visits: node.get_visits(),
+                wins: node.wins.load(Ordering::Relaxed),
+                amaf_visits: node.amaf_visits.load(Ordering::Relaxed),
+                amaf_wins: node.amaf_wins.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
 
-impl MonteCarloSearch {
-    pub fn new(board: BoardState, colour: Colour) -> Self {
-        let root_node = MonteCarloNode {
-            state: board.into(),
-            parent: None,
-            children: Vec::new(),
-            wins: 0,
-            visits: 0,
-            colour,
-            id: 0,
-            game_move: None,
-        };
+    /// returns the statistics of every move from the root, added up with those of the helper trees
+    pub fn get_merged_stats(&self) -> Vec<MoveStats> {
+        let mut merged = self.get_root_stats();
 
-        MonteCarloSearch {
-            nodes: vec![root_node],
-            root: 0,
+        for stats in self.helpers.iter().flat_map(|helper| helper.get_root_stats()) {
+            match merged.iter_mut().find(|existing| existing.turn == stats.turn) {
+                Some(existing) => existing.merge(&stats),
+                None => merged.push(stats),
+            }
+        }
+        merged
+    }
+
+    /// returns the win rate of the player to move at the root and how many playouts it comes from, added up with the helper trees
+    pub fn get_root_win_rate(&self) -> (f64, usize) {
+        let (wins, visits) = std::iter::once(self).chain(&self.helpers).fold((0, 0), |(wins, visits), search| {
+            let nodes = search.nodes.read().unwrap();
+            (wins + nodes[search.root].wins.load(Ordering::Relaxed), visits + nodes[search.root].get_visits())
+        });
+
+        // the root's wins are counted for the player who moved into it
+        (1.0 - wins as f64 / (WIN_POINTS * visits.max(1)) as f64, visits)
+    }
+
+    /// Returns the analysis of every move explored from the root, most visited first
+    ///
+    /// the statistics are added up across the trees of a root parallel search,
+    /// but the principal variations only come from this one
+    pub fn get_analysis(&self) -> Vec<MoveAnalysis> {
+        let mut stats: Vec<MoveStats> = self.get_merged_stats().into_iter().filter(|stats| stats.visits > 0).collect();
+        stats.sort_by_key(|stats| std::cmp::Reverse(stats.visits));
+
+        stats.into_iter()
+            .map(|stats| MoveAnalysis {
+                turn: stats.turn,
+                visits: stats.visits,
+                win_rate: stats.get_win_rate(),
+                prior: stats.get_prior(),
+                lower_bound: stats.get_lower_bound(),
+                pv: self.get_principal_variation(stats.turn),
+            })
+            .collect()
+    }
+
+    /// Returns the line of play starting with the given move from the root, following the most visited child each time
+    ///
+    /// the line ends once no reply has been visited more than once, and the first child wins ties
+    pub fn get_principal_variation(&self, first: Turn) -> Vec<Turn> {
+        let nodes = self.nodes.read().unwrap();
+        let mut pv = vec![first];
+
+        let mut current = nodes[self.root].children.iter().copied().find(|&child| nodes[child].game_move == Some(first));
+        while let Some(node) = current {
+            current = nodes[node].children.iter().copied()
+                .filter(|&child| nodes[child].get_visits() >= PV_MIN_VISITS)
+                .rev() // max_by_key keeps the last of equal elements
+                .max_by_key(|&child| nodes[child].get_visits());
+
+            if let Some(child) = current {
+                pv.push(nodes[child].game_move.unwrap());
+            }
         }
+        pv
     }
 
-    pub fn _get_root(&self) -> &MonteCarloNode {
-        &self.nodes[self.root]
+    /// returns how many times the root has been visited
+    pub fn get_root_visits(&self) -> usize {
+        self.nodes.read().unwrap()[self.root].get_visits()
+    }
+
+    /// runs one selection, expansion, simulation and backpropagation, which can happen on many threads at once
+    fn run_iteration(&self, config: &SearchConfig, rng: &mut impl Rng) {
+        // Selection
+        let leaf_index = self.select_leaf(config);
+
+        // Expansion
+        self.expand(leaf_index, config);
+
+        // Simulation, which doesn't hold the lock at all
+        let (outcome, played) = self.simulate(leaf_index, rng);
+
+        // Backpropagation
+        self.backpropagate(leaf_index, outcome, &played);
     }
 
     /// Selection phase of the MCTS
-    fn select_leaf(&self, node_index: usize) -> usize {
-        let node = &self.nodes[node_index];
-    
-        // If the node has no children, return it
-        if node.children.is_empty() {
-            return node_index;
+    /// 
+    /// every node on the way down gets a virtual loss, so other threads are steered away until the result is in
+    fn select_leaf(&self, config: &SearchConfig) -> usize {
+        let nodes = self.nodes.read().unwrap();
+        let mut node_index = self.root;
+
+        loop {
+            let node = &nodes[node_index];
+            node.virtual_loss.fetch_add(VIRTUAL_LOSS, Ordering::Relaxed);
+
+            // If the node has no children (or the game is over), return it
+            if node.children.is_empty() || node.is_terminal() {
+                return node_index;
+            }
+
+            // the value of children that haven't been visited, the node's win rate is for the player who moved into it
+            let first_play = match config.fpu {
+                Fpu::Infinite => f64::MAX,
+                Fpu::Value(value) => value,
+                Fpu::Reduction(reduction) => 1.0 - node.get_win_rate() - reduction,
+            };
+
+            // Select the child with the highest UCT value, the first child winning ties so unvisited moves are tried in order and pass last
+            let log_parent_visits = ((node.get_visits() + node.virtual_loss.load(Ordering::Relaxed)) as f64).ln();
+            node_index = *node.children.iter()
+                .rev()
+                .max_by(|&&a, &&b| {
+                    let uct_a = Self::calculate_uct(&nodes[a], log_parent_visits, first_play, config);
+                    let uct_b = Self::calculate_uct(&nodes[b], log_parent_visits, first_play, config);
+                    uct_a.partial_cmp(&uct_b).unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .unwrap();
         }
-    
-        // Select the child with the highest UCT value
-        let log_parent_visits = (node.visits as f64).ln();
-        let best_child_index = node.children.par_iter()
-            .max_by(|&a, &b| {
-                let uct_a = self.calculate_uct(*a, log_parent_visits);
-                let uct_b = self.calculate_uct(*b, log_parent_visits);
-                uct_a.partial_cmp(&uct_b).unwrap_or(std::cmp::Ordering::Equal)
-            })
-            .unwrap_or_else(|| {
-                panic!("No children in the current node of the MCTS tree");
-            });
-    
-        // Recursively select the best child
-        self.select_leaf(*best_child_index)
     }
 
     /// Calculate the UCT for the given node
-    fn calculate_uct(&self, node_index: usize, log_parent_visits: f64) -> f64 {
-        let node = &self.nodes[node_index];
+    /// 
+    /// with RAVE the win rate is blended with the AMAF win rate, which is trusted less as the node gets more visits
+    fn calculate_uct(node: &MonteCarloNode, log_parent_visits: f64, first_play: f64, config: &SearchConfig) -> f64 {
+        let visits = node.get_visits() + node.virtual_loss.load(Ordering::Relaxed);
 
-        if node.visits == 0 { // make sure all nodes get explored at least once
-            return f64::MAX;
+        if visits == 0 { // with infinite first play urgency every node gets explored at least once
+            return first_play;
         }
 
-        let win_ratio = node.wins as f64 / node.visits as f64;
-        let exploration = 2.0 * (log_parent_visits / node.visits as f64).sqrt();
-        win_ratio + (exploration/1.1)
+        let mut win_ratio = node.wins.load(Ordering::Relaxed) as f64 / (WIN_POINTS * visits) as f64;
+        let amaf_visits = node.amaf_visits.load(Ordering::Relaxed);
+        if config.rave_equivalence > 0.0 && amaf_visits > 0 {
+            let amaf_ratio = node.amaf_wins.load(Ordering::Relaxed) as f64 / (WIN_POINTS * amaf_visits) as f64;
+            let beta = (config.rave_equivalence / (3.0 * visits as f64 + config.rave_equivalence)).sqrt();
+            win_ratio = (1.0 - beta) * win_ratio + beta * amaf_ratio;
+        }
+        win_ratio + config.exploration * (log_parent_visits / visits as f64).sqrt()
     }
 
     /// Expansion phase of the MCTS
-    fn expand(&mut self, node_index: usize) {
-        let node_state = Arc::clone(&self.nodes[node_index].state);
-        let node_colour = self.nodes[node_index].colour;
+    /// 
+    /// the children are made before the node list is locked for writing, so other threads can keep searching.
+    /// once the tree has reached the node limit leaves are simulated without being expanded
+    fn expand(&self, node_index: usize, config: &SearchConfig) {
+        let (node_state, node_colour, node_passes) = {
+            let nodes = self.nodes.read().unwrap();
+            let node = &nodes[node_index];
+
+            if node.is_terminal() || !node.children.is_empty() || nodes.len() >= config.max_nodes {
+                return; // the game is over, the node has already been expanded or the tree is full
+            }
+            (Arc::clone(&node.state), node.colour, node.passes)
+        };
 
         // Generate all possible moves from the current state
-        let possible_moves = GameState::get_all_possible_moves_for_board(&node_state, node_colour);
-        //possible_moves.shuffle(&mut *self.rng.lock().unwrap()); // for now - could implement a heuristic
+        let mut children: Vec<(Arc<BoardState>, Turn, usize)> = GameState::get_all_possible_moves_for_board(&node_state, node_colour).into_iter()
+            .map(|game_move| (Arc::new(node_state.add_stone(game_move, node_colour).unwrap()), Turn::Move(game_move), 0))
+            .collect();
+
+        // passing is always possible, it leaves the board the same and gives the turn to the opponent
+        children.push((Arc::new(node_state.pass(node_colour)), Turn::Pass, node_passes + 1));
+
+        let mut nodes = self.nodes.write().unwrap();
+        if !nodes[node_index].children.is_empty() {
+            return; // another thread expanded it first
+        }
 
         // For each move, create a new node and add it to the tree
-        for game_move in possible_moves {
-            let new_state = Arc::new(node_state.add_stone(game_move, node_colour).unwrap());
-            let new_node = MonteCarloNode {
-                state: new_state,
-                parent: Some(node_index),
-                children: Vec::new(),
-                wins: 0,
-                visits: 0,
-                colour: node_colour.swap_turn(),
-                id: self.nodes.len(),
-                game_move: Some(game_move),
-            };
-            self.nodes.push(new_node);
+        let first_child = nodes.len();
+        for (state, game_move, passes) in children {
+            let id = nodes.len();
+            nodes.push(MonteCarloNode::new(state, Some(node_index), node_colour.swap_turn(), id, Some(game_move), passes));
         }
 
         // Update the children of the node
-        let children_ids: Vec<usize> = (node_index+1..self.nodes.len()).collect();
-        self.nodes[node_index].children = children_ids;
+        nodes[node_index].children = (first_child..nodes.len()).collect();
     }
 
-    fn simulate(&self, node_index: usize) -> Outcome {
-        let mut current_state = self.nodes[node_index].state.clone();
-        let mut colour = self.nodes[node_index].colour;
-        let mut consecutive_passes = 0;
-    
-        // Loop until the game is over
-        while !GameState::is_game_over(&current_state) {
-            // Select a move using the default policy (in this case, a random move)
-            let possible_moves = GameState::get_all_possible_moves_for_board(&current_state, colour);
-            
-            let game_move = if GameState::check_useful_points_played(&current_state, colour) {
-                // If there are no possible moves, simulate a pass
-                consecutive_passes += 1;
-                None
-            } else {
-                consecutive_passes = 0;
-                Some(self.select_random_move(&possible_moves))
-            };
-    
-            // Apply the move to get the new state
-            if let Some(game_move) = game_move {
-                current_state = current_state.add_stone(game_move, colour).unwrap().into();
-            }
-    
-            // If there are two consecutive passes, the game is over
-            if consecutive_passes >= 2 {
-                break;
-            }
-    
-            colour = colour.swap_turn();
-        }
-    
+    /// Simulation phase of the MCTS, returning the outcome and the points played in the playout
+    fn simulate(&self, node_index: usize, rng: &mut impl Rng) -> (Outcome, Vec<(usize, Colour)>) {
+        let (state, colour, passes) = {
+            let nodes = self.nodes.read().unwrap();
+            let node = &nodes[node_index];
+            (Arc::clone(&node.state), node.colour, node.passes)
+        };
+
+        let mut playout = PlayoutBoard::from_board(&state);
+        let played = playout.play_random_game(colour, passes, rng);
+
         // Return the outcome of the game
-        GameState::determine_outcome(&current_state)
+        (playout.get_outcome(), played)
     }
 
-    fn select_random_move(&self, possible_moves: &[Coordinate]) -> Coordinate {
-        let mut rng = rand::thread_rng();
-        possible_moves.choose(&mut rng).unwrap().clone()
-    }
+    /// Backpropagation phase of the MCTS
+    /// 
+    /// every node on the path gets a visit in place of its virtual loss, and a win (or half of one for a draw) if the player that moved into it won.
+    /// the children of each node on the path also get AMAF statistics if their player went on
+    /// to play their point first, further down the tree or in the playout
+    fn backpropagate(&self, leaf_index: usize, outcome: Outcome, played: &[(usize, Colour)]) {
+        let nodes = self.nodes.read().unwrap();
+        let size = nodes[leaf_index].state.size;
+
+        // who played each point first, which moves earlier as the path is walked back up the tree
+        let mut first_played = vec![Colour::Empty; size * size];
+        for &(point, colour) in played.iter().rev() {
+            first_played[point] = colour;
+        }
+
+        let mut current_index = Some(leaf_index);
 
-    fn backpropagate(&mut self, leaf_index: usize, outcome: Outcome) {
-        let mut current_index = leaf_index;
+        // Loop until the root node has been updated
+        while let Some(index) = current_index {
+            let node = &nodes[index];
+            node.visits.fetch_add(1, Ordering::Relaxed);
+            node.virtual_loss.fetch_sub(VIRTUAL_LOSS, Ordering::Relaxed);
+            node.wins.fetch_add(outcome.get_points(node.colour.swap_turn()), Ordering::Relaxed); // node.colour is the player to move next
 
-        // Loop until the root node is reached
-        while let Some(parent_index) = self.nodes[current_index].parent {
-            // Update the visits and wins of the parent node
-            self.nodes[parent_index].visits += 1;
-            if self.nodes[current_index].colour == outcome.into_colour() {
-                self.nodes[parent_index].wins += 1;
+            for &child in &node.children {
+                if let Some(Turn::Move(coordinate)) = nodes[child].game_move {
+                    if first_played[coordinate.get_index()] == node.colour {
+                        nodes[child].amaf_visits.fetch_add(1, Ordering::Relaxed);
+                        nodes[child].amaf_wins.fetch_add(outcome.get_points(node.colour), Ordering::Relaxed);
+                    }
+                }
+            }
+
+            // the move into this node was played before anything below it
+            if let Some(Turn::Move(coordinate)) = node.game_move {
+                first_played[coordinate.get_index()] = node.colour.swap_turn();
             }
 
             // Move to the parent node
-            current_index = parent_index;
+            current_index = node.parent;
         }
+    }
 
-        // Update the visits and wins of the root node
-        self.nodes[current_index].visits += 1;
-        if self.nodes[current_index].colour == outcome.into_colour() {
-            self.nodes[current_index].wins += 1;
+    /// returns true if the root of the tree is the given position with the given colour to play
+    /// 
+    /// the ko point, the history the superko rules look back through and the rules all have to match too,
+    /// as any of them change which moves can be played and who wins
+    pub fn matches(&self, board: &BoardState, colour: Colour, passes: usize) -> bool {
+        let nodes = self.nodes.read().unwrap();
+        let root = &nodes[self.root].state;
+        nodes[self.root].colour == colour && nodes[self.root].passes == passes
+            && root.hash() == board.hash()
+            && root.rules == board.rules
+            && root.zobrist_table.same_history(&board.zobrist_table)
+    }
+
+    /// Moves the root to the child reached by the given move, keeping its subtree
+    /// 
+    /// returns false if the move hasn't been explored from the root. Helper trees that haven't
+    /// explored it are rebuilt by the next search
+    pub fn advance(&mut self, game_move: Turn) -> bool {
+        for helper in &mut self.helpers {
+            helper.advance(game_move);
+        }
+
+        let nodes = self.nodes.get_mut().unwrap();
+        let child = nodes[self.root].children.iter()
+            .copied()
+            .find(|&child| nodes[child].game_move == Some(game_move));
+
+        match child {
+            Some(child) => {
+                self.prune(child);
+                true
+            }
+            None => false,
         }
     }
 
     /// Modify the MCTS tree to move the root to some new node
     /// 
-    /// This also adjusts every child node's id, parent, and children
+    /// This keeps only the subtree of the new root and re-indexes every node's id, parent, and children
     pub fn prune(&mut self, new_root: usize) {
-        if new_root >= self.nodes.len() {
+        let nodes = self.nodes.get_mut().unwrap();
+        if new_root >= nodes.len() {
             panic!("Error: Attempted to prune with a non-existent node index: {}", new_root);
         }
-    
-        // Adjust the ids, parents, and children of the nodes
-        for node in &mut self.nodes {
-            node.id -= new_root;
-            if let Some(parent) = node.parent {
-                node.parent = Some(parent - new_root);
-            }
-            node.children = node.children.iter().map(|&child| child - new_root).collect();
+
+        // collect the subtree breadth first so that every parent comes before its children
+        let mut order = vec![new_root];
+        let mut i = 0;
+        while i < order.len() {
+            order.extend(nodes[order[i]].children.iter().copied());
+            i += 1;
         }
-    
-        // Keep the nodes from new_root onwards and discard the rest
-        self.nodes = self.nodes.split_off(new_root);
-    
-        // Set the root to the new_root (which is now at index 0)
+
+        let mut new_ids = vec![usize::MAX; nodes.len()];
+        for (new_id, &old_id) in order.iter().enumerate() {
+            new_ids[old_id] = new_id;
+        }
+
+        let mut old_nodes: Vec<Option<MonteCarloNode>> = std::mem::take(nodes).into_iter().map(Some).collect();
+
+        *nodes = order.iter().map(|&old_id| {
+            let mut node = old_nodes[old_id].take().unwrap();
+            node.id = new_ids[old_id];
+            node.parent = if old_id == new_root { None } else { node.parent.map(|parent| new_ids[parent]) };
+            node.children = node.children.iter().map(|&child| new_ids[child]).collect();
+            node
+        }).collect();
+
+        // the new root is now at index 0
         self.root = 0;
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::KoRule;
+
+    /// plays the points in order, alternating from black
+    fn play(size: usize, points: &[usize]) -> BoardState {
+        let mut board = BoardState::new(size).unwrap();
+        for (i, &point) in points.iter().enumerate() {
+            let colour = if i % 2 == 0 { Colour::Black } else { Colour::White };
+            board = board.add_stone(Coordinate::Index(point, size), colour).unwrap();
+        }
+        board
+    }
+
+    #[test]
+    fn prune_keeps_a_scattered_subtree_linked() {
+        let config = SearchConfig::new();
+        let mut search = MonteCarloSearch::new(BoardState::new(3).unwrap(), Colour::Black, 0);
+
+        // expand nodes in an order that spreads the subtree of the new root through the list
+        search.expand(0, &config);
+        search.expand(5, &config);
+        search.expand(2, &config);
+        let grandchild = search.nodes.read().unwrap()[2].children[3];
+        search.expand(grandchild, &config);
+        search.expand(7, &config);
+
+        // the visits of each node are its old index so they can be followed through the prune
+        let nodes = search.nodes.get_mut().unwrap();
+        for (i, node) in nodes.iter().enumerate() {
+            node.visits.store(i, Ordering::Relaxed);
+        }
+        let old_parents: Vec<Option<usize>> = nodes.iter().map(|node| node.parent).collect();
+        let old_children: Vec<Vec<usize>> = nodes.iter().map(|node| node.children.clone()).collect();
+        let old_moves: Vec<Option<Turn>> = nodes.iter().map(|node| node.game_move).collect();
+        let kept = 1 + old_children[2].len() + old_children[grandchild].len();
+
+        search.prune(2);
+        let nodes = search.nodes.read().unwrap();
+
+        assert_eq!(search.root, 0);
+        assert_eq!(nodes.len(), kept);
+        assert_eq!(nodes[0].parent, None);
+        assert_eq!(nodes[0].get_visits(), 2);
+
+        for (i, node) in nodes.iter().enumerate() {
+            let old = node.get_visits();
+            assert_eq!(node.id, i);
+            assert_eq!(node.game_move, old_moves[old]);
+
+            let children: Vec<usize> = node.children.iter().map(|&child| nodes[child].get_visits()).collect();
+            assert_eq!(children, old_children[old]);
+            for &child in &node.children {
+                assert_eq!(nodes[child].parent, Some(i));
+                assert!(child > i);
+            }
+            if let Some(parent) = node.parent {
+                assert_eq!(Some(nodes[parent].get_visits()), old_parents[old]);
+            }
+        }
+    }
+
+    #[test]
+    fn trees_are_only_reused_for_the_same_position() {
+        let board = play(5, &[0, 1]);
+        let search = MonteCarloSearch::new(board.clone(), Colour::Black, 0);
+        assert!(search.matches(&board, Colour::Black, 0));
+        assert!(search.matches(&play(5, &[0, 1]), Colour::Black, 0));
+        assert!(!search.matches(&board, Colour::White, 0));
+        assert!(!search.matches(&board, Colour::Black, 1));
+
+        let mut komi = board.clone();
+        komi.rules.komi += 1.0;
+        assert!(!search.matches(&komi, Colour::Black, 0));
+
+        let mut superko = board.clone();
+        superko.rules.ko = KoRule::SituationalSuperko;
+        assert!(!search.matches(&superko, Colour::Black, 0));
+
+        // the same stones reached by a different line have a different history
+        let transposed = play(5, &[0, 3]).pass(Colour::Black).add_stone(Coordinate::Index(1, 5), Colour::White).unwrap();
+        assert_eq!(transposed.get_grid()[3], Colour::White);
+        assert!(!search.matches(&transposed, Colour::Black, 0));
+    }
+
+    #[test]
+    fn trees_are_not_reused_across_a_ko() {
+        // black takes the ko at 6, after which white can't retake at 7
+        let ko = play(5, &[2, 1, 8, 5, 12, 11, 24, 7, 6]);
+        let search = MonteCarloSearch::new(ko.clone(), Colour::White, 0);
+
+        let setup = BoardState::from_colours(&ko.get_grid(), 5, Colour::White).unwrap();
+        assert_eq!(setup.get_grid(), ko.get_grid());
+        assert!(!search.matches(&setup, Colour::White, 0));
+        assert!(search.matches(&ko, Colour::White, 0));
+    }
+
+    #[test]
+    fn handicap_stones_are_set_up_with_white_to_play() {
+        let mut game = GameState::new(9).unwrap();
+        let points = game.place_fixed_handicap(4).unwrap();
+
+        let root = &game.game_tree.get_root().board;
+        assert!(points.iter().all(|point| root.get_grid()[point.get_index()] == Colour::Black));
+        assert_eq!(root.zobrist_table.get_to_move(), Colour::White);
+        assert_eq!(root.rules, game.board_state.rules);
+        assert!(matches!(game.place_fixed_handicap(4), Err(HandicapErrors::GameStarted)));
+    }
+}
@@ -1,28 +1,320 @@
 use eframe::{egui, App, Frame, NativeOptions};
 use egui::Id;
 
-use crate::board_state::BoardState;
-use crate::colour::Colour;
-use crate::colour;
-use crate::coordinate::Coordinate;
-use crate::game_state::GameState;
-use crate::turn::Turn;
+use go_rs::board_state::BoardState;
+use go_rs::colour::Colour;
+use go_rs::colour;
+use go_rs::coordinate::Coordinate;
+use crate::cli::Options;
+use go_rs::config::{Fpu, Parallelism, SearchConfig, Selection};
+use go_rs::game_state::{GameState, MoveAnalysis};
+use go_rs::gtp;
+use go_rs::player::{Player, PlayerKind};
+use go_rs::turn::Turn;
+use crate::worker::SearchWorker;
+
+const SGF_PATH: &str = "game.sgf"; // where games are saved to and loaded from
+const CANDIDATES_SHOWN: usize = 5; // how many of the moves being considered are marked on the board
+const PV_SHOWN: usize = 8; // how many moves of each principal variation are shown
 
 struct MyApp {
     game: GameState,
+    players: [Option<Box<dyn Player>>; 2], // the players of black and white, None for a human
+    autoplay: Option<[Option<Box<dyn Player>>; 2]>, // players that take over while a game is played out
+    search: Option<SearchWorker>, // the player choosing its turn in the background
+    config: SearchConfig, // for the MCTS of games played out with C, and given to every player when it is changed
+    show_settings: bool, // the search settings window, opened with O
+    show_analysis: bool, // the moves the search is considering, toggled with I
+    kinds: [PlayerKind; 2], // what the players of black and white are, so one that fails can be made again
+    failure: Option<String>, // why the game was stopped, which holds it until a new game is started
+    last_analysis: Option<(Colour, Vec<MoveAnalysis>)>, // what the last finished search thought of its moves
+}
+
+impl MyApp {
+    /// returns where the player of the colour is kept, preferring any game being played out
+    fn slot(&mut self, colour: Colour) -> &mut Option<Box<dyn Player>> {
+        let players = self.autoplay.as_mut().unwrap_or(&mut self.players);
+        &mut players[colour.into_usize() - 1]
+    }
+
+    /// returns every player that isn't currently choosing a turn
+    fn all_players(&mut self) -> impl Iterator<Item = &mut Box<dyn Player>> {
+        self.players.iter_mut().chain(self.autoplay.iter_mut().flatten()).flatten()
+    }
+
+    /// starts the player of the colour to play choosing its turn, unless it is a human
+    fn start_turn(&mut self) {
+        if self.search.is_some() || self.failure.is_some() || self.game.check_end() {
+            return;
+        }
+
+        let colour = self.game.turn;
+        if let Some(player) = self.slot(colour).take() {
+            self.search = Some(SearchWorker::start(player, &self.game));
+        }
+    }
+
+    /// plays a turn and tells every other player about it, returning false if it couldn't be played
+    fn play(&mut self, turn: Turn) -> bool {
+        let colour = self.game.turn;
+        if !self.game.play_turn(turn) {
+            return false;
+        }
+
+        for player in self.all_players() {
+            player.notify(turn, colour);
+        }
+        true
+    }
+
+    /// plays a human move, letting the other player reply
+    fn play_human_turn(&mut self, turn: Turn) {
+        if self.failure.is_none() && self.play(turn) {
+            self.start_turn();
+        }
+    }
+
+    /// tells every player to continue from the current position after it changed other than by a turn
+    fn sync_players(&mut self) {
+        let game = self.game.clone();
+        for player in self.all_players() {
+            player.new_game(&game);
+        }
+    }
+
+    /// makes again any player that was lost when its search failed, letting the game carry on
+    fn restore_players(&mut self) {
+        for (player, kind) in self.players.iter_mut().zip(&self.kinds) {
+            if player.is_none() {
+                *player = kind.create().ok().flatten();
+            }
+        }
+        self.failure = None;
+    }
+
+    /// starts a new game with the same rules as the current one
+    fn new_game(&mut self) {
+        self.stop();
+        self.restore_players();
+        let rules = self.game.get_rules();
+        self.game = GameState::new(self.game.size).expect("the size of the current game");
+        self.game.set_rules(rules);
+        self.last_analysis = None;
+        self.sync_players();
+    }
+
+    /// plays out the rest of the game with the given players
+    fn start_autoplay(&mut self, players: [PlayerKind; 2]) {
+        let mut autoplay = players.map(|kind| kind.create().ok().flatten());
+        for player in autoplay.iter_mut().flatten() {
+            player.new_game(&self.game);
+        }
+
+        self.autoplay = Some(autoplay);
+        self.start_turn();
+    }
+
+    /// plays the turn chosen by a finished search, and starts the next player's turn
+    fn step(&mut self) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let Some(result) = search.poll() else {
+            return; // still thinking
+        };
+
+        let colour = search.colour;
+        self.last_analysis = Some((colour, std::mem::take(&mut search.analysis)));
+        self.search = None;
+
+        // a player that failed has no turn to play, so the game stops rather than moving for it
+        let (turn, player) = match result {
+            Ok(finished) => finished,
+            Err(error) => {
+                self.fail(format!("{}'s player failed: {}", colour.get_string(), error));
+                return;
+            }
+        };
+
+        *self.slot(colour) = Some(player);
+        if !self.play(turn) { // nor does it play something else for a player that chose an illegal move
+            self.fail(format!("{}'s player chose an illegal move ({})", colour.get_string(), gtp::vertex_string(turn)));
+            return;
+        }
+
+        if self.autoplay.is_some() && self.game.check_end() {
+            self.autoplay = None;
+        }
+        self.start_turn();
+    }
+
+    /// stops the game because a player couldn't choose a legal turn, until a new game is started
+    fn fail(&mut self, reason: String) {
+        let failure = format!("{}. Press R for a new game.", reason);
+        eprintln!("{}", failure);
+        self.failure = Some(failure);
+        self.autoplay = None;
+    }
+
+    /// shows the window of search settings, which can't be changed while a player is thinking
+    fn settings_window(&mut self, ctx: &egui::Context, enabled: bool) {
+        let mut config = self.config;
+        let mut open = self.show_settings;
+
+        egui::Window::new("Search settings").open(&mut open).resizable(false).show(ctx, |ui| {
+            ui.add_enabled_ui(enabled, |ui| {
+                egui::Grid::new("search settings").num_columns(2).show(ui, |ui| {
+                    let mut seconds = config.max_time.as_secs_f32();
+                    ui.label("Time per move");
+                    if ui.add(egui::DragValue::new(&mut seconds).speed(0.1).clamp_range(0.1..=600.0).suffix("s")).changed() {
+                        config.max_time = std::time::Duration::from_secs_f32(seconds);
+                    }
+                    ui.end_row();
+
+                    ui.label("Iterations");
+                    ui.add(egui::DragValue::new(&mut config.max_iterations).speed(100).clamp_range(1..=10_000_000));
+                    ui.end_row();
+
+                    ui.label("Node limit");
+                    ui.add(egui::DragValue::new(&mut config.max_nodes).speed(1000).clamp_range(1..=100_000_000));
+                    ui.end_row();
+
+                    ui.label("Threads");
+                    ui.add(egui::DragValue::new(&mut config.threads).clamp_range(1..=256));
+                    ui.end_row();
+
+                    ui.label("Parallelism");
+                    egui::ComboBox::from_id_source("parallelism").selected_text(config.parallelism.get_name()).show_ui(ui, |ui| {
+                        ui.selectable_value(&mut config.parallelism, Parallelism::Tree, Parallelism::Tree.get_name());
+                        ui.selectable_value(&mut config.parallelism, Parallelism::Root, Parallelism::Root.get_name());
+                    });
+                    ui.end_row();
+
+                    ui.label("Exploration");
+                    ui.add(egui::DragValue::new(&mut config.exploration).speed(0.01).clamp_range(0.0..=10.0));
+                    ui.end_row();
+
+                    ui.label("RAVE equivalence");
+                    ui.add(egui::DragValue::new(&mut config.rave_equivalence).speed(10.0).clamp_range(0.0..=100_000.0));
+                    ui.end_row();
+
+                    ui.label("First play urgency");
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("fpu").selected_text(match config.fpu {
+                            Fpu::Infinite => "infinite",
+                            Fpu::Value(_) => "win rate",
+                            Fpu::Reduction(_) => "parent less",
+                        }).show_ui(ui, |ui| {
+                            if ui.selectable_label(config.fpu == Fpu::Infinite, "infinite").clicked() {
+                                config.fpu = Fpu::Infinite;
+                            }
+                            if ui.selectable_label(matches!(config.fpu, Fpu::Value(_)), "win rate").clicked() {
+                                config.fpu = Fpu::Value(0.5);
+                            }
+                            if ui.selectable_label(matches!(config.fpu, Fpu::Reduction(_)), "parent less").clicked() {
+                                config.fpu = Fpu::Reduction(0.2);
+                            }
+                        });
+                        if let Fpu::Value(value) | Fpu::Reduction(value) = &mut config.fpu {
+                            ui.add(egui::DragValue::new(value).speed(0.01).clamp_range(0.0..=1.0));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Move selection");
+                    egui::ComboBox::from_id_source("selection").selected_text(config.selection.get_name()).show_ui(ui, |ui| {
+                        for selection in [Selection::MostVisits, Selection::MaxRobust, Selection::Lcb, Selection::WinRate] {
+                            ui.selectable_value(&mut config.selection, selection, selection.get_name());
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Resign below");
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut config.resign_threshold).speed(0.01).clamp_range(0.0..=1.0));
+                        ui.label("for");
+                        ui.add(egui::DragValue::new(&mut config.resign_moves).clamp_range(1..=100).suffix(" moves"));
+                    });
+                    ui.end_row();
+                });
+            });
+        });
+
+        self.show_settings = open;
+        if config != self.config {
+            self.config = config;
+            for player in self.all_players() {
+                player.set_config(&config);
+            }
+        }
+    }
+
+    /// stops any search or game being played out
+    fn stop(&mut self) {
+        if let Some(search) = self.search.take() {
+            let colour = search.colour;
+            *self.slot(colour) = search.cancel();
+        }
+        self.autoplay = None;
+    }
 }
 
 impl App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut Frame) {
-        let MyApp {game: _} = self;
+        self.step();
+        let thinking = self.search.as_ref().map(|search| (search.colour, search.iterations, search.elapsed));
+        if thinking.is_some() {
+            ctx.request_repaint(); // keep updating without any input
+        }
+
+        // the search going on, or else the last one to finish
+        let analysis = match &self.search {
+            Some(search) => Some((search.colour, search.analysis.clone())),
+            None => self.last_analysis.clone(),
+        }.filter(|_| self.show_analysis);
+
+        // only the search going on is for the position on the board
+        let candidates: Vec<MoveAnalysis> = match (&analysis, thinking) {
+            (Some((_, analysis)), Some(_)) => analysis.iter().take(CANDIDATES_SHOWN).cloned().collect(),
+            _ => Vec::new(),
+        };
+        let analysis_lines: Vec<String> = analysis.iter()
+            .flat_map(|(colour, analysis)| analysis.iter().take(3).map(move |analysis| {
+                let pv: Vec<String> = analysis.pv.iter().take(PV_SHOWN).map(|&turn| gtp::vertex_string(turn)).collect();
+                format!("{} {}: {:.1}% after {} visits, {}", colour.get_string(), gtp::vertex_string(analysis.turn), analysis.win_rate * 100.0, analysis.visits, pv.join(" "))
+            }))
+            .collect();
 
         let (turn, boardstate) = self.game.game_tree.get_board();
         let last_move_indicator = self.game.game_tree.get_last_move();
 
         let grid_state = boardstate.get_grid().clone();
 
-        let move_string = turn.get_string() + " to play.";
-        let turn_string = format!("{}/{}", self.game.game_tree.get_pointer(), self.game.game_tree.get_length());
+        let scoring = self.game.in_scoring_phase();
+        let dead_stones = self.game.dead_stones.clone();
+        let score = scoring.then(|| self.game.score());
+        let move_string = if let Some(score) = &score {
+            let result = match score.get_winner() {
+                (Colour::Empty, _) => String::from("the game is a draw"),
+                (winner, margin) => format!("{} wins by {}", winner.get_string(), margin),
+            };
+            format!("Scoring: {}. Click groups to mark them dead, Enter to accept.", result)
+        } else {
+            turn.get_string() + " to play."
+        };
+
+        // the territory of each colour once the dead stones are taken off
+        let territory: Vec<(Colour, Coordinate)> = if scoring {
+            let scoring_board = self.game.get_scoring_board();
+            let scoring_grid = scoring_board.get_grid();
+            [Colour::Black, Colour::White].into_iter()
+                .flat_map(|colour| scoring_board.get_territory(&scoring_grid, colour).into_iter().flat_map(move |group| group.get_positions().into_iter().map(move |point| (colour, point))))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let (variation, variations) = self.game.game_tree.get_variation();
+        let turn_string = format!("{}/{} (variation {}/{})", self.game.game_tree.get_pointer(), self.game.game_tree.get_length(), variation, variations);
 
         egui::CentralPanel::default().show(ctx, |ui| {
             // Calculate the size of each cell in the grid
@@ -55,11 +347,16 @@ impl App for MyApp {
                 // Draw stones here!
                 for i in 0..self.game.size {
                     for j in 0..self.game.size {
-                        let color = match &grid_state[Coordinate::Position((i, j)).get_index()] {
+                        let coordinate = Coordinate::Position((i, j), self.game.size);
+                        let mut color = match &grid_state[coordinate.get_index()] {
                             colour::Colour::White => egui::Color32::WHITE,
                             colour::Colour::Black => egui::Color32::BLACK,
                             colour::Colour::Empty => continue, // Skip empty positions
                         };
+
+                        if dead_stones.contains(&coordinate) { // dead stones are drawn faded
+                            color = color.gamma_multiply(0.35);
+                        }
                 
                         let center = egui::pos2(
                             j as f32 * cell_size + cell_size / 2.0,
@@ -71,6 +368,16 @@ impl App for MyApp {
                     }
                 }
 
+                for (colour, point) in &territory { // small squares mark each colour's territory
+                    let center = egui::pos2(
+                        point.get_position().1 as f32 * cell_size + cell_size / 2.0,
+                        point.get_position().0 as f32 * cell_size + cell_size / 2.0,
+                    );
+                    let fill = if *colour == Colour::Black {egui::Color32::BLACK} else {egui::Color32::WHITE};
+                    let rect = egui::Rect::from_center_size(center, egui::vec2(cell_size / 4.0, cell_size / 4.0));
+                    shapes.push(egui::Shape::rect_filled(rect, 0.0, fill));
+                }
+
                 if let Some(move_coordinate) = last_move_indicator { // indicates the last move
                     let center = egui::pos2(
                         move_coordinate.get_position().1 as f32 * cell_size + cell_size / 2.0,
@@ -80,10 +387,46 @@ impl App for MyApp {
                     shapes.push(egui::Shape::circle_stroke(center, cell_size / 5.0, egui::Stroke::new(2.5, indicator_colour)));
                 }
 
+                // the moves being considered are marked with their win rates, the best one in blue
+                let mut labels = Vec::new();
+                for (rank, analysis) in candidates.iter().enumerate() {
+                    let Turn::Move(coordinate) = analysis.turn else {
+                        continue;
+                    };
+                    let center = egui::pos2(
+                        coordinate.get_position().1 as f32 * cell_size + cell_size / 2.0,
+                        coordinate.get_position().0 as f32 * cell_size + cell_size / 2.0,
+                    );
+                    let fill = if rank == 0 {egui::Color32::from_rgb(80, 140, 230)} else {egui::Color32::from_gray(200)};
+                    shapes.push(egui::Shape::circle_filled(center, cell_size / 2.6, fill.gamma_multiply(0.8)));
+                    labels.push((center, format!("{:.0}", analysis.win_rate * 100.0), egui::Color32::BLACK));
+                }
+
+                // and the line the best one is expected to lead to is numbered in the colour of each move
+                if let (Some(best), Some((colour, _, _))) = (candidates.first(), thinking) {
+                    let mut mover = colour;
+                    for (number, turn) in best.pv.iter().take(PV_SHOWN).enumerate().skip(1) {
+                        mover = mover.swap_turn();
+                        let Turn::Move(coordinate) = turn else {
+                            continue;
+                        };
+                        let center = egui::pos2(
+                            coordinate.get_position().1 as f32 * cell_size + cell_size / 2.0,
+                            coordinate.get_position().0 as f32 * cell_size + cell_size / 2.0,
+                        );
+                        let text_colour = if mover == Colour::Black {egui::Color32::BLACK} else {egui::Color32::WHITE};
+                        shapes.push(egui::Shape::circle_stroke(center, cell_size / 3.0, egui::Stroke::new(1.5, text_colour)));
+                        labels.push((center, (number + 1).to_string(), text_colour));
+                    }
+                }
+
                 ui.painter().extend(shapes);
+                for (center, text, colour) in labels {
+                    ui.painter().text(center, egui::Align2::CENTER_CENTER, text, egui::FontId::proportional(cell_size / 3.5), colour);
+                }
             });
 
-            if response.clicked() {
+            if response.clicked() && thinking.is_none() && self.failure.is_none() {
 
                 let y_pos = (response.interact_pointer_pos().unwrap().y - response.rect.min.y).max(0.0);
                 let x_pos = (response.interact_pointer_pos().unwrap().x - response.rect.min.x).max(0.0);
@@ -93,10 +436,14 @@ impl App for MyApp {
 
                 let coords = self.game.clamp_coordinate(i, j);
 
-                self.game.play_turn(Turn::Move(coords));
+                if scoring {
+                    self.game.toggle_dead_group(coords);
+                } else {
+                    self.play_human_turn(Turn::Move(coords));
+                }
             }
 
-            if response.secondary_clicked() {
+            if response.secondary_clicked() && thinking.is_none() && self.failure.is_none() {
                 let y_pos = (response.interact_pointer_pos().unwrap().y - response.rect.min.y).max(0.0);
                 let x_pos = (response.interact_pointer_pos().unwrap().x - response.rect.min.x).max(0.0);
    
@@ -105,8 +452,10 @@ impl App for MyApp {
             
                 let coords = self.game.clamp_coordinate(i, j);
 
-                self.game.play_turn(Turn::Move(coords));
-                self.game.random_completed_game();
+                if self.play(Turn::Move(coords)) { // both colours play random moves from here
+                    let seed = self.config.seed;
+                    self.start_autoplay([PlayerKind::Random(seed), PlayerKind::Random(seed)]);
+                }
             }
 
             if response.middle_clicked() {
@@ -124,30 +473,126 @@ impl App for MyApp {
 
                        
             ui.heading(move_string);
+            if let Some(failure) = &self.failure {
+                ui.colored_label(egui::Color32::RED, failure);
+            }
+            if let Some((colour, iterations, elapsed)) = thinking {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(format!("{} is thinking: {} iterations in {:.1}s. F to force a move, Esc to cancel.", colour.get_string(), iterations, elapsed.as_secs_f32()));
+                });
+            }
+            for line in &analysis_lines {
+                ui.small(line);
+            }
             ui.weak(turn_string);
+            if let Some(score) = &score { // territory, captures and komi of each colour
+                ui.label(score.to_string());
+            }
 
         });
 
+        self.settings_window(ctx, thinking.is_none());
+
         ctx.input(|i| {
+            if i.key_pressed(egui::Key::Escape) {
+                self.stop();
+            }
+
+            if i.key_pressed(egui::Key::F) {
+                if let Some(search) = &self.search {
+                    search.force();
+                }
+            }
+
+            if i.key_pressed(egui::Key::R) {
+                self.new_game();
+                self.start_turn();
+            }
+
+            if i.key_pressed(egui::Key::L) {
+                self.stop();
+                match std::fs::read_to_string(SGF_PATH).map(|text| GameState::from_sgf(&text)) {
+                    Ok(Ok(game)) => {
+                        self.game = game;
+                        self.restore_players();
+                        self.sync_players();
+                        self.start_turn();
+                    }
+                    Ok(Err(error)) => println!("Failed to read {}: {}", SGF_PATH, error),
+                    Err(error) => println!("Failed to load game: {}", error),
+                }
+            }
+
+            if i.key_pressed(egui::Key::O) {
+                self.show_settings = !self.show_settings;
+            }
+
+            if i.key_pressed(egui::Key::I) {
+                self.show_analysis = !self.show_analysis;
+            }
+
+            if i.key_pressed(egui::Key::S) {
+                match std::fs::write(SGF_PATH, self.game.export_sgf()) {
+                    Ok(_) => println!("Saved game to {}", SGF_PATH),
+                    Err(error) => println!("Failed to save game: {}", error),
+                }
+            }
+
+            if self.search.is_some() || self.autoplay.is_some() { // the position can't change while the engine is playing
+                return;
+            }
+
             let direction = i.scroll_delta.y; // check for vertical scroll
 
             if direction > 0.0 {
                 self.game.jump_back();
+                self.sync_players();
             } else if direction < 0.0 {
                 self.game.jump_forward();
+                self.sync_players();
+            }
+
+            if i.key_pressed(egui::Key::ArrowUp) {
+                self.game.switch_variation(false);
+                self.sync_players();
+            }
+
+            if i.key_pressed(egui::Key::ArrowDown) {
+                self.game.switch_variation(true);
+                self.sync_players();
+            }
+
+            if i.key_pressed(egui::Key::Enter) && self.game.in_scoring_phase() {
+                self.game.accept_score();
+                println!("Final result: {}", self.game.game_tree.info.result.clone().unwrap_or_default());
             }
 
             if i.key_pressed(egui::Key::A) {
-                self.game.play_turn(Turn::Pass);
+                self.play_human_turn(Turn::Pass);
             }
 
-            if i.key_pressed(egui::Key::R) {
-                self.game = GameState::new(self.game.size);
+            if i.key_pressed(egui::Key::H) && self.game.game_tree.get_root().children.is_empty() { // adds a handicap stone before the first move
+                let stones = (self.game.game_tree.info.handicap + 1).max(2);
+                self.new_game();
+                match self.game.place_free_handicap(stones) {
+                    Ok(_) => println!("Playing with {} handicap stones", stones),
+                    Err(error) => println!("Can't place {} handicap stones: {}", stones, error),
+                }
+                self.sync_players();
+                self.start_turn(); // white plays first after handicap
             }
 
             if i.key_pressed(egui::Key::P) {
-                let value = self.game.calculate_total_completed_score();
-                println!("{:?}", value);
+                println!("{}", self.game.score());
+            }
+
+            if i.key_pressed(egui::Key::K) { // cycle through the scoring rules
+                let mut rules = self.game.get_rules();
+                rules.scoring = rules.scoring.next();
+                self.game.set_rules(rules);
+                self.sync_players();
+                println!("Scoring with {} rules", rules.scoring.get_string());
             }
 
             if i.key_pressed(egui::Key::Space) {
@@ -156,21 +601,49 @@ impl App for MyApp {
 
             if i.key_pressed(egui::Key::V) {
                 let colours = self.game.board_state.get_grid();
-                let new_state = BoardState::from_colours(&colours, self.game.size);
+                let mut new_state = BoardState::from_colours(&colours, self.game.size, self.game.turn).expect("the grid of the current game");
+                new_state.rules = self.game.get_rules();
+                new_state.prisoners = self.game.board_state.prisoners;
                 self.game.board_state = new_state;
+                self.sync_players();
             }
 
-            if i.key_pressed(egui::Key::C) {
-                self.game.weak_play();
+            if i.key_pressed(egui::Key::C) { // black plays random moves against the MCTS
+                self.start_autoplay([PlayerKind::Random(self.config.seed), PlayerKind::Mcts(self.config)]);
             }
         });
     }
 }
 
-pub fn run(size: usize) -> Result<(), eframe::Error> {
-    let app = MyApp {
-        game: GameState::new(size),
+pub fn run(options: &Options) -> Result<(), eframe::Error> {
+    // the bot plays its colour against the opponent, otherwise two humans play
+    let (black, white) = match options.bot_colour {
+        Some(Colour::Black) => (&options.player, &options.opponent),
+        Some(_) => (&options.opponent, &options.player),
+        None => (&PlayerKind::Human, &PlayerKind::Human),
+    };
+    let players = match (black.create(), white.create()) {
+        (Ok(black), Ok(white)) => [black, white],
+        (Err(error), _) | (_, Err(error)) => {
+            eprintln!("Failed to start a player: {}", error);
+            return Ok(());
+        }
+    };
+
+    let mut app = MyApp {
+        game: options.new_game(),
+        players,
+        autoplay: None,
+        search: None,
+        config: options.search,
+        show_settings: false,
+        show_analysis: true,
+        kinds: [black.clone(), white.clone()],
+        failure: None,
+        last_analysis: None,
     };
+    app.sync_players();
+    app.start_turn();
 
     let native_options = NativeOptions {
         initial_window_size: Some(egui::vec2(450.0, 450.0)),
@@ -1,4 +1,4 @@
-use crate::{colour::Colour, coordinate::Coordinate, board_state::BoardState};
+use crate::{colour::Colour, coordinate::Coordinate};
 use std::{collections::HashSet, hash::Hash};
 
 #[derive(Clone, Debug)]
@@ -6,59 +6,55 @@ pub struct GroupState {
     id: usize,
     pub colour: Colour,
     pub points: HashSet<Coordinate>,
+    pub liberties: HashSet<Coordinate>, // the empty points next to the group, kept up to date as stones are played
 }
 
 impl GroupState {
-    /// creates a new groupstate given an id, position and colour of size 1
-    pub fn new(id: usize, colour: Colour, position: Coordinate) -> Self {
-        GroupState { id, colour, points: HashSet::from_iter(vec![position].into_iter()) }
+    /// creates a new groupstate given an id, position, colour and the empty points around it of size 1
+    pub fn new(id: usize, colour: Colour, position: Coordinate, liberties: HashSet<Coordinate>) -> Self {
+        GroupState { id, colour, points: HashSet::from_iter(vec![position]), liberties }
     }
 
     /// merges multiple groupstates into a single group
-    pub fn merge_groups(id: usize, groups: &Vec<&GroupState>) -> GroupState {
+    /// 
+    /// the liberties are combined, so any point now filled by a stone of the group has to be removed after
+    pub fn merge_groups(id: usize, groups: &[&GroupState]) -> GroupState {
         let colour = groups[0].colour; // make a more robust check in the future
 
         let points: HashSet<Coordinate> = HashSet::from_iter(groups.iter().flat_map(|group| group.points.iter().cloned()));
+        let liberties: HashSet<Coordinate> = HashSet::from_iter(groups.iter().flat_map(|group| group.liberties.iter().cloned()));
 
         GroupState {
             id,
             colour, 
             points,
+            liberties,
         }
     }
 
-    pub fn combine_groups(groups: &Vec<GroupState>) -> HashSet<Coordinate> {
+    pub fn combine_groups(groups: &[GroupState]) -> HashSet<Coordinate> {
         let points: HashSet<Coordinate> = HashSet::from_iter(groups.iter().flat_map(|group| group.points.iter().cloned()));
 
         points
     }
 
-    /// returns true if there are more than 0 liberties (calculated every time)
-    pub fn check_liberties(&self, grid: &Vec<Colour>, board_size: usize) -> bool {
-        let mut liberties: HashSet<Coordinate> = HashSet::new();
+    /// returns true if there are more than 0 liberties
+    pub fn check_liberties(&self) -> bool {
+        !self.liberties.is_empty()
+    }
 
-        for position in &self.points {
-            for adjacent in BoardState::get_adjacent_indices(board_size, *position)  {
-                if grid[adjacent.get_index()] == Colour::Empty {
-                    liberties.insert(adjacent);
-                }
-            }
-        }
-        return liberties.len() > 0;
+    /// returns how many liberties the group has
+    pub fn calculate_liberties(&self) -> usize {
+        self.liberties.len()
     }
 
-    /// debug function to check how many liberties a group has
-    pub fn calculate_liberties(&self, grid: &Vec<Colour>, board_size: usize) -> usize {
-        let mut liberties: HashSet<Coordinate> = HashSet::new();
+    /// returns true if the group can be captured with one more stone
+    pub fn in_atari(&self) -> bool {
+        self.liberties.len() == 1
+    }
 
-        for position in &self.points {
-            for adjacent in BoardState::get_adjacent_indices(board_size, *position)  {
-                if grid[adjacent.get_index()] == Colour::Empty {
-                    liberties.insert(adjacent);
-                }
-            }
-        }
-        return liberties.len()
+    pub fn get_liberties(&self) -> &HashSet<Coordinate> {
+        &self.liberties
     }
 
     /// returns true if the group contains the given position
@@ -71,7 +67,7 @@ impl GroupState {
     }
 
     pub fn get_positions(&self) -> Vec<Coordinate> {
-        return self.points.iter().cloned().collect();
+        self.points.iter().cloned().collect()
     }
 
     pub fn get_colour(&self) -> Colour {
@@ -87,6 +83,7 @@ impl GroupState {
             id: 777, // making number - shoot me
             colour: Colour::Empty,
             points,
+            liberties: HashSet::new(),
         }
     }
 }
@@ -96,8 +93,8 @@ impl GroupState {
 // as long as the id is always unique for each subsequent new group and i remove the old ones from
 // the board struct the id of the group is the only thing needed
 impl PartialEq for GroupState {
-    fn eq(&self, other: &Self) -> bool {
-        return self.id == other.id
+    fn eq(&self, other: &Self) -> bool { // empty groups all share an id so the points have to be compared too
+        self.id == other.id && self.points == other.points
     }
 }
 
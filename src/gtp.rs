@@ -0,0 +1,577 @@
+// a headless engine that speaks the Go Text Protocol (version 2) over stdin/stdout
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Duration;
+
+use crate::board_state::MAX_BOARD_SIZE;
+use crate::colour::Colour;
+use crate::config::{SearchConfig, TimeControl};
+use crate::coordinate::Coordinate;
+use crate::fails::BoardErrors;
+use crate::game_state::{GameState, MoveAnalysis};
+use crate::player::Player;
+use crate::rules::Rules;
+use crate::turn::Turn;
+
+const COLUMNS: &str = "ABCDEFGHJKLMNOPQRSTUVWXYZ"; // gtp skips the letter I
+const ANALYZE_INTERVAL: u64 = 100; // centiseconds between lines of analysis when the controller doesn't say
+
+const COMMANDS: [&str; 29] = [
+    "protocol_version",
+    "name",
+    "version",
+    "known_command",
+    "list_commands",
+    "quit",
+    "boardsize",
+    "clear_board",
+    "komi",
+    "play",
+    "genmove",
+    "reg_genmove",
+    "undo",
+    "final_score",
+    "final_status_list",
+    "showboard",
+    "loadsgf",
+    "printsgf",
+    "kgs-rules",
+    "fixed_handicap",
+    "place_free_handicap",
+    "set_free_handicap",
+    "time_left",
+    "time_settings",
+    "kgs-time_settings",
+    "go-rs-show_config",
+    "go-rs-set_config",
+    "lz-analyze",
+    "kata-analyze",
+];
+
+pub struct GtpEngine {
+    game: GameState,
+    rules: Rules, // kept between games as controllers usually only send the komi once
+    seed: Option<u64>,
+    config: SearchConfig, // the search parameters of the player, changed with go-rs-set_config
+    player: Box<dyn Player>, // chooses the engine's moves
+}
+
+impl GtpEngine {
+    /// creates an engine that plays the given player on a board of the given size, with the search parameters it was given
+    pub fn new(size: usize, rules: Rules, config: SearchConfig, player: Box<dyn Player>) -> Result<Self, BoardErrors> {
+        let mut engine = GtpEngine {
+            game: GameState::new(size)?,
+            rules,
+            seed: config.seed,
+            config,
+            player,
+        };
+        engine.new_game(size);
+        Ok(engine)
+    }
+
+    /// starts a new game on a board of the given size
+    fn new_game(&mut self, size: usize) {
+        self.game = GameState::new(size).expect("the size is checked by new and boardsize");
+        self.game.set_rules(self.rules);
+        if let Some(seed) = self.seed {
+            self.game.set_seed(seed);
+        }
+        self.player.new_game(&self.game);
+    }
+
+    /// Handles a single command, returning the response or an error message
+    pub fn handle(&mut self, command: &str, args: &[&str]) -> Result<String, String> {
+        match command {
+            "protocol_version" => Ok(String::from("2")),
+            "name" => Ok(String::from("go-rs")),
+            "version" => Ok(String::from(env!("CARGO_PKG_VERSION"))),
+            "known_command" => {
+                let known = args.first().is_some_and(|name| COMMANDS.contains(name));
+                Ok(known.to_string())
+            }
+            "list_commands" => Ok(COMMANDS.join("\n")),
+            "quit" => Ok(String::new()),
+            "boardsize" => {
+                let size: usize = parse_argument(args, 0)?;
+                if !(2..=MAX_BOARD_SIZE).contains(&size) {
+                    return Err(String::from("unacceptable size"));
+                }
+                self.new_game(size);
+                Ok(String::new())
+            }
+            "clear_board" => {
+                self.new_game(self.game.size);
+                Ok(String::new())
+            }
+            "komi" => {
+                self.rules.komi = parse_argument(args, 0)?;
+                self.game.set_rules(self.rules);
+                self.player.new_game(&self.game);
+                Ok(String::new())
+            }
+            "play" => {
+                let colour = parse_colour(args.first())?;
+                let turn = parse_vertex(args.get(1).ok_or("missing vertex")?, self.game.size)?;
+
+                self.game.turn = colour;
+                if !self.game.play_turn(turn) {
+                    return Err(String::from("illegal move"));
+                }
+                self.player.notify(turn, colour);
+                Ok(String::new())
+            }
+            "genmove" | "reg_genmove" => {
+                let colour = parse_colour(args.first())?;
+                if self.game.turn != colour { // the controller wants the same colour to play twice
+                    self.game.turn = colour;
+                    self.player.new_game(&self.game);
+                }
+                let turn = self.player.genmove(&self.game, &AtomicBool::new(false), &mut |_, _, _| {});
+
+                if command == "reg_genmove" || !self.game.play_turn(turn) {
+                    self.player.new_game(&self.game); // the player assumed its turn was played
+                }
+                Ok(vertex_string(turn))
+            }
+            "fixed_handicap" | "place_free_handicap" => {
+                let stones: usize = parse_argument(args, 0)?;
+                let points = if command == "fixed_handicap" {
+                    self.game.place_fixed_handicap(stones)
+                } else {
+                    self.game.place_free_handicap(stones)
+                };
+
+                let vertices: Vec<String> = points.map_err(|error| error.to_string())?
+                    .into_iter()
+                    .map(|point| vertex_string(Turn::Move(point)))
+                    .collect();
+                self.player.new_game(&self.game);
+                Ok(vertices.join(" "))
+            }
+            "set_free_handicap" => {
+                let mut points = Vec::new();
+                for vertex in args {
+                    match parse_vertex(vertex, self.game.size)? {
+                        Turn::Move(point) => points.push(point),
+                        _ => return Err(String::from("bad vertex list")),
+                    }
+                }
+
+                self.game.place_handicap(&points).map_err(|error| error.to_string())?;
+                self.player.new_game(&self.game);
+                Ok(String::new())
+            }
+            "time_left" => {
+                let colour = parse_colour(args.first())?;
+                let seconds: f32 = parse_argument(args, 1)?;
+                let stones: usize = parse_argument(args, 2)?;
+                let time = Duration::try_from_secs_f32(seconds).map_err(|_| String::from("syntax error"))?;
+
+                self.player.time_left(colour, time, stones);
+                Ok(String::new())
+            }
+            "time_settings" => {
+                let main = parse_seconds(args, 0)?;
+                let period = parse_seconds(args, 1)?;
+                let stones: usize = parse_argument(args, 2)?;
+
+                let time_control = match (period.is_zero(), stones) {
+                    (true, _) => TimeControl::Absolute(main),
+                    (false, 0) => TimeControl::None, // byo-yomi without any stones means there is no limit
+                    (false, stones) => TimeControl::Canadian(main, period, stones),
+                };
+                self.player.time_settings(time_control);
+                Ok(String::new())
+            }
+            "kgs-time_settings" => {
+                let time_control = match args.first().map(|system| system.to_lowercase()).as_deref() {
+                    Some("none") => TimeControl::None,
+                    Some("absolute") => TimeControl::Absolute(parse_seconds(args, 1)?),
+                    Some("byoyomi") => TimeControl::ByoYomi(parse_seconds(args, 1)?, parse_seconds(args, 2)?, parse_argument(args, 3)?),
+                    Some("canadian") => TimeControl::Canadian(parse_seconds(args, 1)?, parse_seconds(args, 2)?, parse_argument(args, 3)?),
+                    _ => return Err(String::from("syntax error")),
+                };
+                self.player.time_settings(time_control);
+                Ok(String::new())
+            }
+            "go-rs-show_config" => Ok(self.config.to_string()),
+            "go-rs-set_config" => {
+                let name = args.first().ok_or("missing parameter")?;
+                let value = args.get(1).ok_or("missing value")?;
+                self.config.set(name, value).map_err(|error| error.to_string())?;
+
+                self.seed = self.config.seed;
+                self.player.set_config(&self.config);
+                Ok(String::new())
+            }
+            "undo" => {
+                if !self.game.undo() {
+                    return Err(String::from("cannot undo"));
+                }
+                self.player.new_game(&self.game);
+                Ok(String::new())
+            }
+            "final_score" => {
+                self.propose_dead_stones();
+                Ok(self.game.score().get_result())
+            }
+            "final_status_list" => {
+                self.propose_dead_stones();
+                let dead = match args.first().map(|status| status.to_lowercase()).as_deref() {
+                    Some("dead") => true,
+                    Some("alive") => false,
+                    Some("seki") => return Ok(String::new()), // seki is not detected
+                    _ => return Err(String::from("syntax error")),
+                };
+
+                let grid = self.game.board_state.get_grid();
+                let stones: Vec<String> = (0..grid.len())
+                    .map(|index| Coordinate::Index(index, self.game.size))
+                    .filter(|&point| grid[point.get_index()] != Colour::Empty && self.game.dead_stones.contains(&point) == dead)
+                    .map(|point| vertex_string(Turn::Move(point)))
+                    .collect();
+                Ok(stones.join(" "))
+            }
+            "showboard" => Ok(self.show_board()),
+            "loadsgf" => {
+                let path = args.first().ok_or("missing filename")?;
+                let text = std::fs::read_to_string(path).map_err(|_| String::from("cannot load file"))?;
+                let mut game = GameState::from_sgf(&text).map_err(|error| format!("cannot load file ({})", error))?;
+
+                // the optional move number is the move to be played next, the rest of the game is kept in the tree
+                if let Some(move_number) = args.get(1) {
+                    let move_number: usize = move_number.parse().map_err(|_| String::from("syntax error"))?;
+                    while game.game_tree.get_pointer() >= move_number.max(1) && game.game_tree.get_pointer() > 0 {
+                        game.jump_back();
+                    }
+                }
+
+                self.rules = game.get_rules();
+                if let Some(seed) = self.seed {
+                    game.set_seed(seed);
+                }
+                self.game = game;
+                self.player.new_game(&self.game);
+                Ok(String::new())
+            }
+            "printsgf" => Ok(self.game.export_sgf().trim_end().to_string()), // the response ends with its own blank line
+            "kgs-rules" => {
+                let preset = args.first().and_then(|name| Rules::from_name(name)).ok_or("unknown rules")?;
+                self.rules = Rules { komi: self.rules.komi, ..preset }; // komi is sent separately
+                self.game.set_rules(self.rules);
+                self.player.new_game(&self.game);
+                Ok(String::new())
+            }
+            _ => Err(String::from("unknown command")),
+        }
+    }
+
+    /// Handles lz-analyze and kata-analyze, which answer straight away and then print a line of analysis every interval
+    ///
+    /// the search carries on until another command arrives on lines, which is returned to be handled next
+    fn analyze(&mut self, command: &str, args: &[&str], id: &str, lines: &Receiver<String>, stdout: &mut impl Write) -> std::io::Result<Option<String>> {
+        let (colour, interval) = match parse_analyze_arguments(args, self.game.turn) {
+            Ok(arguments) => arguments,
+            Err(error) => {
+                write!(stdout, "?{} {}\n\n", id, error)?;
+                stdout.flush()?;
+                return Ok(None);
+            }
+        };
+
+        let mut game = self.game.clone();
+        game.turn = colour;
+
+        let stop = AtomicBool::new(false);
+        let mut next = None;
+        let mut answered = false;
+        let mut last_update = None;
+        let mut result = Ok(());
+
+        let analysed = self.player.analyze(&game, &stop, &mut |_, elapsed, analysis| {
+            if !answered {
+                answered = true;
+                result = writeln!(stdout, "={}", id).and_then(|_| stdout.flush());
+            }
+
+            if !stop.load(Ordering::Relaxed) { // otherwise the next command has already arrived
+                match lines.try_recv() {
+                    Ok(line) => {
+                        next = Some(line);
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                    Err(TryRecvError::Disconnected) => stop.store(true, Ordering::Relaxed),
+                    Err(TryRecvError::Empty) => {}
+                }
+            }
+
+            if result.is_ok() && !stop.load(Ordering::Relaxed) && last_update.is_none_or(|last_update| elapsed - last_update >= interval) {
+                let analysis = analysis();
+                if !analysis.is_empty() { // an empty line would end the response
+                    last_update = Some(elapsed);
+                    result = writeln!(stdout, "{}", analysis_string(command, &analysis)).and_then(|_| stdout.flush());
+                }
+            }
+        });
+        result?;
+
+        if !analysed {
+            write!(stdout, "?{} cannot analyze\n\n", id)?;
+        } else if answered {
+            writeln!(stdout)?;
+        } else {
+            write!(stdout, "={}\n\n", id)?;
+        }
+        stdout.flush()?;
+        Ok(next)
+    }
+
+    /// proposes dead stones if the controller asks for the score before the game ended by passing
+    fn propose_dead_stones(&mut self) {
+        if !self.game.in_scoring_phase() {
+            self.game.dead_stones = self.game.propose_dead_stones();
+        }
+    }
+
+    /// draws the board as text with black as X and white as O
+    fn show_board(&self) -> String {
+        let size = self.game.size;
+        let grid = self.game.board_state.get_grid();
+        let letters: String = COLUMNS.chars().take(size).map(|c| format!(" {}", c)).collect();
+        let mut board = format!("\n   {}\n", letters);
+
+        for x in 0..size {
+            let row = size - x;
+            let points: String = (0..size).map(|y| match grid[Coordinate::Position((x, y), size).get_index()] {
+                Colour::Black => " X",
+                Colour::White => " O",
+                Colour::Empty => " .",
+            }).collect();
+            board.push_str(&format!("{:>2} {} {}\n", row, points, row));
+        }
+
+        board.push_str(&format!("   {}", letters));
+        board
+    }
+}
+
+/// parses the argument at the given index
+fn parse_argument<T: std::str::FromStr>(args: &[&str], index: usize) -> Result<T, String> {
+    args.get(index).and_then(|arg| arg.parse().ok()).ok_or(String::from("syntax error"))
+}
+
+/// parses the argument at the given index as a number of seconds
+fn parse_seconds(args: &[&str], index: usize) -> Result<Duration, String> {
+    let seconds: f32 = parse_argument(args, index)?;
+    Duration::try_from_secs_f32(seconds).map_err(|_| String::from("syntax error"))
+}
+
+/// Parses the optional colour and interval in centiseconds of lz-analyze and kata-analyze
+///
+/// the interval can also be given as "interval N", any other options are ignored
+fn parse_analyze_arguments(args: &[&str], turn: Colour) -> Result<(Colour, Duration), String> {
+    let mut args = args.iter().peekable();
+    let colour = match args.peek().map(|&arg| parse_colour(Some(arg))) {
+        Some(Ok(colour)) => {
+            args.next();
+            colour
+        }
+        _ => turn,
+    };
+
+    let mut interval = args.next_if(|arg| arg.parse::<u64>().is_ok()).map(|arg| arg.parse().unwrap());
+    while let Some(&arg) = args.next() {
+        if arg == "interval" {
+            interval = Some(args.next().and_then(|arg| arg.parse().ok()).ok_or("syntax error")?);
+        }
+    }
+    Ok((colour, Duration::from_millis(interval.unwrap_or(ANALYZE_INTERVAL) * 10)))
+}
+
+/// formats the analysis as a line of lz-analyze output, where rates are out of 10000, or of kata-analyze, where they are out of 1
+fn analysis_string(command: &str, analysis: &[MoveAnalysis]) -> String {
+    let rate = |rate: f64| if command == "kata-analyze" {
+        format!("{:.6}", rate)
+    } else {
+        ((rate * 10000.0).round() as usize).to_string()
+    };
+
+    let moves: Vec<String> = analysis.iter().enumerate().map(|(order, analysis)| {
+        let pv: Vec<String> = analysis.pv.iter().map(|&turn| vertex_string(turn)).collect();
+        format!("info move {} visits {} winrate {} prior {} lcb {} order {} pv {}", vertex_string(analysis.turn), analysis.visits, rate(analysis.win_rate), rate(analysis.prior), rate(analysis.lower_bound), order, pv.join(" "))
+    }).collect();
+    moves.join(" ")
+}
+
+/// parses a gtp colour ie "b", "white"
+pub fn parse_colour(colour: Option<&&str>) -> Result<Colour, String> {
+    match colour.map(|colour| colour.to_lowercase()).as_deref() {
+        Some("b") | Some("black") => Ok(Colour::Black),
+        Some("w") | Some("white") => Ok(Colour::White),
+        _ => Err(String::from("invalid color")),
+    }
+}
+
+/// parses a gtp vertex ie "D4" or "pass" into a turn
+pub fn parse_vertex(vertex: &str, size: usize) -> Result<Turn, String> {
+    let vertex = vertex.to_uppercase();
+
+    if vertex == "PASS" {
+        return Ok(Turn::Pass);
+    }
+    if vertex == "RESIGN" {
+        return Ok(Turn::Resign);
+    }
+
+    let mut chars = vertex.chars();
+    let column = chars.next().and_then(|letter| COLUMNS.find(letter));
+    let row: Option<usize> = chars.as_str().parse().ok();
+
+    match (column, row) {
+        (Some(column), Some(row)) if column < size && row >= 1 && row <= size => {
+            Ok(Turn::Move(Coordinate::Position((size - row, column), size)))
+        }
+        _ => Err(String::from("invalid vertex")),
+    }
+}
+
+/// converts a turn into a gtp vertex
+pub fn vertex_string(turn: Turn) -> String {
+    match turn {
+        Turn::Move(coordinate) => {
+            let (x, y) = coordinate.get_position();
+            format!("{}{}", COLUMNS.as_bytes()[y] as char, coordinate.get_size() - x)
+        }
+        Turn::Pass => String::from("pass"),
+        Turn::Resign => String::from("resign"),
+    }
+}
+
+/// removes comments and control characters, returning the id, command and arguments of a line
+fn preprocess(line: &str) -> Option<(Option<usize>, String, Vec<String>)> {
+    let line = line.split('#').next().unwrap_or("");
+    let line: String = line.chars()
+        .filter(|c| !c.is_control() || *c == '\t')
+        .map(|c| if c == '\t' { ' ' } else { c })
+        .collect();
+
+    let mut words = line.split_whitespace();
+    let mut first = words.next()?;
+
+    let id = first.parse().ok();
+    if id.is_some() {
+        first = words.next()?;
+    }
+
+    Some((id, first.to_lowercase(), words.map(String::from).collect()))
+}
+
+/// Runs the engine, reading commands from stdin until "quit" or the input closes
+pub fn run(mut engine: GtpEngine) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout();
+
+    // stdin is read on its own thread so a command can arrive while the engine is analysing
+    let (sender, lines) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut next = None;
+    while let Some(line) = next.take().or_else(|| lines.recv().ok()) {
+        let Some((id, command, args)) = preprocess(&line) else {
+            continue;
+        };
+        let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+        let id = id.map(|id| id.to_string()).unwrap_or_default();
+
+        if command == "lz-analyze" || command == "kata-analyze" {
+            next = engine.analyze(&command, &args, &id, &lines, &mut stdout)?;
+            continue;
+        }
+
+        match engine.handle(&command, &args) {
+            Ok(response) => write!(stdout, "={} {}\n\n", id, response)?,
+            Err(error) => write!(stdout, "?{} {}\n\n", id, error)?,
+        }
+        stdout.flush()?;
+
+        if command == "quit" {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::RandomPlayer;
+
+    fn engine(size: usize) -> GtpEngine {
+        GtpEngine::new(size, Rules::default(), SearchConfig::new(), Box::new(RandomPlayer::new(Some(1)))).unwrap()
+    }
+
+    #[test]
+    fn vertices_skip_the_letter_i() {
+        assert_eq!(parse_vertex("H1", 19), Ok(Turn::Move(Coordinate::Position((18, 7), 19))));
+        assert_eq!(parse_vertex("j1", 19), Ok(Turn::Move(Coordinate::Position((18, 8), 19))));
+        assert_eq!(parse_vertex("A19", 19), Ok(Turn::Move(Coordinate::Position((0, 0), 19))));
+        assert!(parse_vertex("I5", 19).is_err());
+
+        assert_eq!(vertex_string(Turn::Move(Coordinate::Position((18, 8), 19))), "J1");
+        assert_eq!(vertex_string(Turn::Move(Coordinate::Position((0, 24), 25))), "Z25");
+    }
+
+    #[test]
+    fn vertices_round_trip() {
+        for size in [5, 9, 19, MAX_BOARD_SIZE] {
+            for index in 0..size * size {
+                let turn = Turn::Move(Coordinate::Index(index, size));
+                assert_eq!(parse_vertex(&vertex_string(turn), size), Ok(turn));
+            }
+        }
+        assert_eq!(parse_vertex("PASS", 9), Ok(Turn::Pass));
+        assert_eq!(parse_vertex("resign", 9), Ok(Turn::Resign));
+    }
+
+    #[test]
+    fn vertices_off_the_board_are_invalid() {
+        for vertex in ["A0", "A10", "K1", "", "A", "11", "A-1"] {
+            assert!(parse_vertex(vertex, 9).is_err(), "{}", vertex);
+        }
+    }
+
+    #[test]
+    fn preprocessing_removes_comments_and_control_characters() {
+        assert_eq!(preprocess("12 play\tb D4 # a comment"), Some((Some(12), String::from("play"), vec![String::from("b"), String::from("D4")])));
+        assert_eq!(preprocess("GENMOVE\x07 w\r"), Some((None, String::from("genmove"), vec![String::from("w")])));
+        assert_eq!(preprocess("# only a comment"), None);
+        assert_eq!(preprocess("   "), None);
+        assert_eq!(preprocess("3"), None);
+    }
+
+    #[test]
+    fn bad_commands_get_error_responses() {
+        let mut engine = engine(9);
+
+        assert_eq!(engine.handle("boardsize", &["1"]), Err(String::from("unacceptable size")));
+        assert_eq!(engine.handle("boardsize", &["26"]), Err(String::from("unacceptable size")));
+        assert!(engine.handle("boardsize", &["nine"]).is_err());
+        assert_eq!(engine.handle("play", &["red", "D4"]), Err(String::from("invalid color")));
+        assert_eq!(engine.handle("play", &["b", "Z9"]), Err(String::from("invalid vertex")));
+        assert!(engine.handle("play", &["b"]).is_err());
+
+        assert_eq!(engine.handle("play", &["b", "D4"]), Ok(String::new()));
+        assert_eq!(engine.handle("play", &["w", "D4"]), Err(String::from("illegal move")));
+        assert_eq!(engine.handle("known_command", &["play"]), Ok(String::from("true")));
+        assert_eq!(engine.handle("known_command", &["dance"]), Ok(String::from("false")));
+        assert_eq!(engine.handle("dance", &[]), Err(String::from("unknown command")));
+    }
+}
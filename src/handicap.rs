@@ -0,0 +1,75 @@
+// where handicap stones are placed at the start of a game
+use crate::coordinate::Coordinate;
+
+pub const MIN_HANDICAP: usize = 2; // a single handicap stone is just black playing first
+
+/// returns the largest fixed handicap for the board, which is 0 if the board is too small for one
+pub fn max_fixed_handicap(size: usize) -> usize {
+    if size < 7 {
+        0
+    } else if size == 7 || size.is_multiple_of(2) { // there is no single centre point to use
+        4
+    } else {
+        9
+    }
+}
+
+/// Returns the standard star point layout for the given number of handicap stones, as used by GTP
+///
+/// returns None if the board doesn't have a fixed layout for that many stones
+pub fn fixed_handicap(size: usize, stones: usize) -> Option<Vec<Coordinate>> {
+    if stones < MIN_HANDICAP || stones > max_fixed_handicap(size) {
+        return None;
+    }
+
+    let edge = if size >= 13 { 3 } else { 2 }; // the 4-4 points on large boards and the 3-3 points on small ones
+    let (low, middle, high) = (edge, size / 2, size - 1 - edge);
+
+    // (row, column) from the top of the board, ie the first point is the bottom left
+    let corners = [(high, low), (low, high), (low, low), (high, high)];
+    let sides = [(middle, low), (middle, high), (high, middle), (low, middle)];
+    let centre = (middle, middle);
+
+    let mut points: Vec<(usize, usize)> = corners.iter().take(stones.min(4)).copied().collect();
+    match stones {
+        5 => points.push(centre),
+        6 => points.extend(&sides[..2]),
+        7 => {
+            points.extend(&sides[..2]);
+            points.push(centre);
+        }
+        8 => points.extend(&sides),
+        9 => {
+            points.extend(&sides);
+            points.push(centre);
+        }
+        _ => {}
+    }
+
+    Some(points.into_iter().map(|position| Coordinate::Position(position, size)).collect())
+}
+
+/// Chooses where to place the given number of free handicap stones
+///
+/// this uses the fixed layout where possible, and otherwise spreads the stones out from the centre
+pub fn free_handicap(size: usize, stones: usize) -> Option<Vec<Coordinate>> {
+    if stones < MIN_HANDICAP || stones >= size * size {
+        return None;
+    }
+
+    let mut points = fixed_handicap(size, stones.min(max_fixed_handicap(size))).unwrap_or_default();
+    let centre = (size - 1) as f32 / 2.0;
+    let distance = |point: &Coordinate| {
+        let (x, y) = point.get_position();
+        (x as f32 - centre).powi(2) + (y as f32 - centre).powi(2)
+    };
+
+    let mut remaining: Vec<Coordinate> = (0..size * size)
+        .map(|index| Coordinate::Index(index, size))
+        .filter(|point| !points.contains(point))
+        .collect();
+    remaining.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+
+    points.extend(remaining.into_iter().take(stones - points.len()));
+    Some(points)
+}
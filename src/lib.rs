@@ -0,0 +1,35 @@
+//! A Go engine built around a persistent board and Monte Carlo tree search.
+//!
+//! The rules and board live in [`rules`], [`board_state`] and [`scoring`], a game with its history in
+//! [`game_state`] and [`tree`], the file formats in [`sgf`] and [`gtp`], and the engine in [`player`]
+//! and [`game_state::MonteCarloSearch`]. The command line, self play and the egui front end (only built with
+//! the `gui` feature) belong to the `go-rs` binary rather than the library.
+
+pub mod coordinate;
+pub mod colour;
+pub mod fails;
+pub mod tree;
+pub mod zobrist;
+pub mod board_state;
+pub mod game_state;
+pub mod group_state;
+pub mod turn;
+pub mod sgf;
+pub mod scoring;
+pub mod rules;
+pub mod handicap;
+pub mod config;
+pub mod gtp;
+pub mod player;
+mod playout; // only the search plays out games
+
+pub use board_state::{BoardState, MAX_BOARD_SIZE};
+pub use colour::Colour;
+pub use config::SearchConfig;
+pub use coordinate::Coordinate;
+pub use game_state::{GameState, MonteCarloSearch, MoveAnalysis};
+pub use player::{Player, PlayerKind};
+pub use rules::Rules;
+pub use scoring::ScoreBreakdown;
+pub use tree::GameTree;
+pub use turn::Turn;
@@ -1,23 +1,85 @@
-//#![allow(unused)]
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
 
-mod coordinate;
+use go_rs::gtp::{self, GtpEngine};
+use go_rs::{GameState, MonteCarloSearch};
 
+use cli::{Mode, Options};
+
+mod cli;
+#[cfg(feature = "gui")]
 mod graphics;
-mod colour;
-mod fails;
-mod tree;
-mod zobrist;
-mod board_state;
-mod game_state;
-mod group_state;
-mod turn;
+mod selfplay;
+#[cfg(feature = "gui")]
+mod worker;
 
 const BOARD_SIZE: usize = 5;
 
-// potentially define MCTS parameters here:
-// ie iterations/time to iterate for
-
 fn main() {
-    println!("running...");
-    graphics::run(BOARD_SIZE).unwrap();
+    let options = match Options::new(BOARD_SIZE).parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    match options.mode {
+        #[cfg(feature = "gui")]
+        Mode::Gui => {
+            println!("running...");
+            graphics::run(&options).unwrap();
+        }
+        #[cfg(not(feature = "gui"))]
+        Mode::Gui => {
+            eprintln!("go-rs was built without the gui, use one of the other modes\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+        Mode::Gtp => run_gtp(&options).unwrap(), // the headless engine for use with gtp controllers
+        Mode::SelfPlay | Mode::Match => {
+            match selfplay::run(&options, [&options.player, &options.opponent], Path::new(&options.output)) {
+                Ok(statistics) => println!("\n{}\n\nGames written to {}", statistics, options.output),
+                Err(error) => eprintln!("Failed to play games into {}: {}", options.output, error),
+            }
+        }
+        Mode::Analyze => analyze(&options),
+    }
+}
+
+/// runs the bot as a gtp engine over stdin/stdout
+fn run_gtp(options: &Options) -> std::io::Result<()> {
+    let invalid = |error: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, error);
+    let player = options.player.create()?.ok_or(invalid(String::from("a human can't play over gtp")))?;
+    let engine = GtpEngine::new(options.size, options.rules, options.search, player).map_err(|error| invalid(error.to_string()))?;
+    gtp::run(engine)
+}
+
+/// prints the moves the MCTS considers for the player to move at the end of the game in the sgf file, with the line it expects each to lead to
+fn analyze(options: &Options) {
+    let Some(path) = &options.file else {
+        eprintln!("analyze needs an sgf file\n\n{}", cli::USAGE);
+        std::process::exit(2);
+    };
+
+    let game = match std::fs::read_to_string(path).map(|text| GameState::from_sgf(&text)) {
+        Ok(Ok(game)) => game,
+        Ok(Err(error)) => return eprintln!("Failed to read {}: {}", path, error),
+        Err(error) => return eprintln!("Failed to load {}: {}", path, error),
+    };
+
+    let passes = game.game_tree.get_consecutive_passes();
+    let search = Arc::new(Mutex::new(MonteCarloSearch::new(game.board_state.clone(), game.turn, passes)));
+    let best = MonteCarloSearch::search(&search, &game.board_state, game.turn, passes, &options.search, &AtomicBool::new(false), |_, _, _| {});
+    println!("{} to play, best move {}", game.turn.get_string(), gtp::vertex_string(best));
+
+    for analysis in search.lock().unwrap().get_analysis().into_iter().take(10) {
+        let pv: Vec<String> = analysis.pv.iter().map(|&turn| gtp::vertex_string(turn)).collect();
+        println!("{:>6} {:>7} visits {:>6.1}% prior {:>6.1}% pv {}", gtp::vertex_string(analysis.turn), analysis.visits, analysis.win_rate * 100.0, analysis.prior * 100.0, pv.join(" "));
+    }
 }
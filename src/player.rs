@@ -0,0 +1,489 @@
+// the players that choose turns, so the gui, selfplay and gtp can pair any two of them
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::board_state::BoardState;
+use crate::colour::Colour;
+use crate::config::{SearchConfig, Selection, TimeControl, LAG_MARGIN};
+use crate::coordinate::Coordinate;
+use crate::game_state::{GameState, MonteCarloSearch, MoveAnalysis};
+use crate::gtp;
+use crate::sgf;
+use crate::tree::GameTree;
+use crate::turn::Turn;
+
+const MIN_MOVES_LEFT: usize = 10; // the fewest moves the time left is shared between
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10); // how long an engine has to answer its first command
+const QUIT_TIMEOUT: Duration = Duration::from_secs(2); // how long an engine has to quit before it is killed
+const MIN_RESIGN_VISITS: usize = 1000; // the fewest playouts from the root the bot will trust enough to resign on
+
+/// called as a player searches with its iterations, the time taken and a way to get the analysis of the search so far
+pub type Progress<'a> = dyn FnMut(usize, Duration, &dyn Fn() -> Vec<MoveAnalysis>) + 'a;
+
+/// something that can choose the turns of one side of a game
+pub trait Player: Send {
+    fn get_name(&self) -> String;
+
+    /// Chooses the turn for the colour to play in the game, which the player assumes is then played
+    ///
+    /// searching players stop early once stop is set, and call progress as they go
+    fn genmove(&mut self, game: &GameState, stop: &AtomicBool, progress: &mut Progress<'_>) -> Turn;
+
+    /// Searches the position of the game for the colour to play until stop is set, without choosing a turn
+    ///
+    /// progress is called as in genmove, returns false if the player can't analyse positions
+    fn analyze(&mut self, _game: &GameState, _stop: &AtomicBool, _progress: &mut Progress<'_>) -> bool {
+        false
+    }
+
+    /// tells the player about a turn it didn't choose itself
+    fn notify(&mut self, _turn: Turn, _colour: Colour) {}
+
+    /// tells the player to continue from the position of the game, after a new game, loading, undoing or handicap
+    fn new_game(&mut self, _game: &GameState) {}
+
+    /// tells the player how much time the colour has left, and how many stones must be played in it (0 for the whole game)
+    fn time_left(&mut self, _colour: Colour, _time: Duration, _stones: usize) {}
+
+    /// tells the player the clock of the game, before any time is left
+    fn time_settings(&mut self, _time_control: TimeControl) {}
+
+    /// changes the search parameters of a player that searches
+    fn set_config(&mut self, _config: &SearchConfig) {}
+}
+
+/// describes a player so one can be created for each front end
+#[derive(Clone, Debug)]
+pub enum PlayerKind {
+    Human, // plays through the gui
+    Random(Option<u64>), // the seed of its moves
+    Mcts(SearchConfig),
+    Gtp(String), // the command that starts the engine
+}
+
+impl PlayerKind {
+    /// reads a player from its name: human, random, mcts or `gtp:<command>`
+    pub fn from_name(name: &str, config: SearchConfig) -> Option<PlayerKind> {
+        if let Some(command) = name.strip_prefix("gtp:") {
+            return (!command.trim().is_empty()).then(|| PlayerKind::Gtp(command.to_string()));
+        }
+
+        match name.to_lowercase().as_str() {
+            "human" => Some(PlayerKind::Human),
+            "random" => Some(PlayerKind::Random(config.seed)),
+            "mcts" => Some(PlayerKind::Mcts(config)),
+            _ => None,
+        }
+    }
+
+    /// Creates the player, which is None for a human
+    pub fn create(&self) -> io::Result<Option<Box<dyn Player>>> {
+        Ok(match self {
+            PlayerKind::Human => None,
+            PlayerKind::Random(seed) => Some(Box::new(RandomPlayer::new(*seed))),
+            PlayerKind::Mcts(config) => Some(Box::new(MctsPlayer::new(*config))),
+            PlayerKind::Gtp(command) => Some(Box::new(GtpPlayer::new(command)?)),
+        })
+    }
+}
+
+/// plays random moves, passing once all the important points have been played
+pub struct RandomPlayer {
+    rng: StdRng,
+}
+
+impl RandomPlayer {
+    pub fn new(seed: Option<u64>) -> Self {
+        RandomPlayer {
+            rng: seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64),
+        }
+    }
+}
+
+impl Player for RandomPlayer {
+    fn get_name(&self) -> String {
+        String::from("Random")
+    }
+
+    fn genmove(&mut self, game: &GameState, _stop: &AtomicBool, _progress: &mut Progress<'_>) -> Turn {
+        if game.board_state.check_all_important_points_played() {
+            return Turn::Pass;
+        }
+
+        let possible_moves = game.get_all_possible_moves(game.turn);
+
+        if possible_moves.len() < 3 { // only eyes are left to fill
+            return Turn::Pass;
+        }
+
+        Turn::Move(possible_moves[self.rng.gen_range(0..possible_moves.len())])
+    }
+}
+
+/// plays the move found by the MCTS, keeping its tree between turns
+pub struct MctsPlayer {
+    config: SearchConfig,
+    mcts: Arc<Mutex<MonteCarloSearch>>,
+    time_control: TimeControl,
+    time_used: Duration, // searching since the time settings were given, for when the controller doesn't say what is left
+    time_left: Option<(Duration, usize)>, // the time and stones left for the controller's clock
+    losing_moves: usize, // how many moves in a row have had a win rate below the resign threshold
+}
+
+impl MctsPlayer {
+    pub fn new(config: SearchConfig) -> Self {
+        MctsPlayer {
+            config,
+            mcts: Arc::new(Mutex::new(MonteCarloSearch::new(BoardState::new(2).unwrap(), Colour::Black, 0))), // replaced by the first search or game
+            time_control: TimeControl::None,
+            time_used: Duration::ZERO,
+            time_left: None,
+            losing_moves: 0,
+        }
+    }
+
+    /// returns the time to search for, sharing any time left between the moves still to be played
+    fn get_move_time(&self, game: &GameState) -> Duration {
+        let empty = game.board_state.get_grid().iter().filter(|&&point| point == Colour::Empty).count();
+        let moves = (empty / 2).max(MIN_MOVES_LEFT);
+
+        let time = match self.time_left {
+            Some((time, stones)) => Some((time / if stones > 0 { stones } else { moves } as u32).saturating_sub(LAG_MARGIN)),
+            None => self.time_control.get_move_time(self.time_used, moves),
+        };
+        time.map_or(self.config.max_time, |time| self.config.max_time.min(time))
+    }
+}
+
+impl Player for MctsPlayer {
+    fn get_name(&self) -> String {
+        format!("MCTS ({} iterations, {:.1}s, exploration {:.2}, rave {}, {} {} parallel threads, {} selection)", self.config.max_iterations, self.config.max_time.as_secs_f32(), self.config.exploration, self.config.rave_equivalence, self.config.threads, self.config.parallelism.get_name(), self.config.selection.get_name())
+    }
+
+    fn genmove(&mut self, game: &GameState, stop: &AtomicBool, progress: &mut Progress<'_>) -> Turn {
+        let config = SearchConfig { max_time: self.get_move_time(game), ..self.config };
+        let passes = game.game_tree.get_consecutive_passes();
+        let start = Instant::now();
+
+        let turn = MonteCarloSearch::search(&self.mcts, &game.board_state, game.turn, passes, &config, stop, |iterations, elapsed, search| {
+            progress(iterations, elapsed, &|| search.get_analysis())
+        });
+        self.time_used += start.elapsed();
+
+        let mut mcts = self.mcts.lock().unwrap();
+        let (win_rate, visits) = mcts.get_root_win_rate();
+        mcts.advance(turn);
+
+        // resign once the position has looked lost for long enough, going by searches big enough to trust
+        if visits >= MIN_RESIGN_VISITS && win_rate < self.config.resign_threshold {
+            self.losing_moves += 1;
+        } else {
+            self.losing_moves = 0;
+        }
+        if self.losing_moves >= self.config.resign_moves.max(1) {
+            return Turn::Resign;
+        }
+        turn
+    }
+
+    /// searches without any limits other than stop, keeping the tree for the next search
+    fn analyze(&mut self, game: &GameState, stop: &AtomicBool, progress: &mut Progress<'_>) -> bool {
+        let config = SearchConfig { max_time: Duration::MAX, max_iterations: usize::MAX, selection: Selection::MostVisits, ..self.config };
+        let passes = game.game_tree.get_consecutive_passes();
+
+        MonteCarloSearch::search(&self.mcts, &game.board_state, game.turn, passes, &config, stop, |iterations, elapsed, search| {
+            progress(iterations, elapsed, &|| search.get_analysis())
+        });
+        true
+    }
+
+    /// keeps the tree in step with the game, it is rebuilt on the next search if the turn wasn't explored
+    fn notify(&mut self, turn: Turn, _colour: Colour) {
+        self.mcts.lock().unwrap().advance(turn);
+    }
+
+    fn new_game(&mut self, game: &GameState) {
+        *self.mcts.lock().unwrap() = MonteCarloSearch::new(game.board_state.clone(), game.turn, game.game_tree.get_consecutive_passes());
+        self.losing_moves = 0;
+    }
+
+    fn time_left(&mut self, _colour: Colour, time: Duration, stones: usize) {
+        self.time_left = Some((time, stones));
+    }
+
+    fn time_settings(&mut self, time_control: TimeControl) {
+        self.time_control = time_control;
+        self.time_used = Duration::ZERO;
+        self.time_left = None;
+    }
+
+    fn set_config(&mut self, config: &SearchConfig) {
+        self.config = *config;
+    }
+}
+
+/// another engine run as a subprocess and spoken to with gtp
+pub struct GtpPlayer {
+    process: Child,
+    input: ChildStdin,
+    lines: Receiver<String>, // the lines the engine writes, read on their own thread so waiting for them can time out
+    name: String,
+    size: usize,
+}
+
+impl GtpPlayer {
+    /// starts the engine with the given command line, ie "gnugo --mode gtp"
+    /// 
+    /// fails if the engine doesn't answer its name within STARTUP_TIMEOUT
+    pub fn new(command: &str) -> io::Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or(io::Error::new(io::ErrorKind::InvalidInput, "empty gtp command"))?;
+
+        let mut process = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let input = process.stdin.take().unwrap();
+        let output = BufReader::new(process.stdout.take().unwrap());
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in output.lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut player = GtpPlayer { process, input, lines, name: program.to_string(), size: 0 };
+
+        let response = player.write("name").and_then(|_| player.read_response(Some(STARTUP_TIMEOUT)));
+        match response {
+            Ok(response) => {
+                if let Ok(name) = parse_response(&response) {
+                    player.name = name;
+                }
+                Ok(player)
+            }
+            Err(error) => {
+                let _ = player.process.kill(); // it might never read the quit it would be sent when dropped
+                Err(io::Error::new(error.kind(), format!("{} didn't start: {}", program, error)))
+            }
+        }
+    }
+
+    /// Sends a command to the engine, returning its response or the error it gave
+    fn send(&mut self, command: &str) -> Result<String, String> {
+        self.write(command).map_err(|error| error.to_string())?;
+        let response = self.read_response(None).map_err(|error| error.to_string())?;
+        parse_response(&response)
+    }
+
+    /// writes a command to the engine without waiting for the response
+    fn write(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.input, "{}", command)?;
+        self.input.flush()
+    }
+
+    /// Reads the lines of a response up to the blank line that ends it, failing if the engine closes its output
+    /// or the timeout passes first
+    fn read_response(&mut self, timeout: Option<Duration>) -> io::Result<String> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut response = String::new();
+
+        loop {
+            let line = match deadline {
+                Some(deadline) => self.lines.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => self.lines.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let line = match line {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => return Err(io::Error::new(io::ErrorKind::TimedOut, "engine didn't respond")),
+                Err(RecvTimeoutError::Disconnected) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "engine closed")),
+            };
+
+            let line = line.trim_end();
+            if line.is_empty() {
+                if response.is_empty() {
+                    continue; // blank lines before the response
+                }
+                return Ok(response);
+            }
+            response.push_str(line);
+            response.push('\n');
+        }
+    }
+
+    /// Sets up the starting position by loading it as an sgf file, returning false if the engine can't
+    fn load_setup(&mut self, game: &GameState) -> bool {
+        if self.send("known_command loadsgf").as_deref() != Ok("true") {
+            return false;
+        }
+
+        let mut setup = GameTree::from_board(game.game_tree.get_root().board.clone());
+        setup.set_first_colour(game.game_tree.get_first_colour());
+
+        let path = std::env::temp_dir().join(format!("go-rs-setup-{}.sgf", std::process::id()));
+        let loaded = std::fs::write(&path, sgf::save_game(&setup)).is_ok() && self.send(&format!("loadsgf {}", path.display())).is_ok();
+        let _ = std::fs::remove_file(&path);
+        loaded
+    }
+
+    /// sends a command whose response isn't needed, reporting any error
+    fn send_checked(&mut self, command: &str) {
+        if let Err(error) = self.send(command) {
+            eprintln!("{} failed \"{}\": {}", self.name, command, error);
+        }
+    }
+}
+
+/// returns the text of a gtp response, or the error it gave, without the = or ? and any id
+fn parse_response(response: &str) -> Result<String, String> {
+    let strip_id = |text: &str| text.trim_start_matches(|c: char| c.is_ascii_digit()).trim().to_string();
+    if let Some(text) = response.strip_prefix('=') {
+        Ok(strip_id(text))
+    } else if let Some(text) = response.strip_prefix('?') {
+        Err(strip_id(text))
+    } else {
+        Err(format!("malformed response \"{}\"", response.trim()))
+    }
+}
+
+impl Player for GtpPlayer {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// the engine resigns if it can't be read or gives back something that isn't a move
+    fn genmove(&mut self, game: &GameState, _stop: &AtomicBool, _progress: &mut Progress<'_>) -> Turn {
+        let colour = if game.turn == Colour::Black { "b" } else { "w" };
+
+        match self.send(&format!("genmove {}", colour)).and_then(|vertex| gtp::parse_vertex(&vertex, self.size)) {
+            Ok(turn) => turn,
+            Err(error) => {
+                eprintln!("{} failed to generate a move: {}", self.name, error);
+                Turn::Resign
+            }
+        }
+    }
+
+    fn notify(&mut self, turn: Turn, colour: Colour) {
+        if turn != Turn::Resign {
+            let colour = if colour == Colour::Black { "b" } else { "w" };
+            self.send_checked(&format!("play {} {}", colour, gtp::vertex_string(turn)));
+        }
+    }
+
+    /// Sets up the engine from scratch, placing any setup stones and replaying every turn so far
+    ///
+    /// black stones alone are sent as handicap, otherwise the position is loaded from an sgf file as gtp can't set up stones.
+    /// engines without loadsgf are sent the stones as moves, black first so nothing is captured, which puts them in their
+    /// ko history and leaves them to think the colours alternated
+    fn new_game(&mut self, game: &GameState) {
+        self.size = game.size;
+        self.send_checked(&format!("boardsize {}", game.size));
+        self.send_checked("clear_board");
+        self.send_checked(&format!("komi {}", game.get_rules().komi));
+
+        let root = game.game_tree.get_root().board.get_grid();
+        let stones = |colour: Colour| -> Vec<Turn> {
+            root.iter().enumerate()
+                .filter(|(_, &point)| point == colour)
+                .map(|(index, _)| Turn::Move(Coordinate::Index(index, game.size)))
+                .collect()
+        };
+        let (black, white) = (stones(Colour::Black), stones(Colour::White));
+
+        if white.is_empty() && black.len() >= 2 {
+            let handicap: Vec<String> = black.into_iter().map(gtp::vertex_string).collect();
+            self.send_checked(&format!("set_free_handicap {}", handicap.join(" ")));
+        } else if (!black.is_empty() || !white.is_empty()) && !self.load_setup(game) {
+            eprintln!("{} can't load sgf files, so its setup stones are played as moves", self.name);
+            for stone in black {
+                self.notify(stone, Colour::Black);
+            }
+            for stone in white {
+                self.notify(stone, Colour::White);
+            }
+        }
+
+        for (turn, colour) in game.game_tree.get_turns() {
+            self.notify(turn, colour);
+        }
+    }
+
+    fn time_left(&mut self, colour: Colour, time: Duration, stones: usize) {
+        let colour = if colour == Colour::Black { "b" } else { "w" };
+        self.send_checked(&format!("time_left {} {} {}", colour, time.as_secs(), stones));
+    }
+
+    /// byo-yomi is sent as canadian overtime of one stone, which is all standard gtp has
+    fn time_settings(&mut self, time_control: TimeControl) {
+        let (main, period, stones) = match time_control {
+            TimeControl::None => (Duration::ZERO, Duration::from_secs(1), 0), // byo-yomi without stones means no limit
+            TimeControl::Absolute(main) => (main, Duration::ZERO, 0),
+            TimeControl::ByoYomi(main, period, _) => (main, period, 1),
+            TimeControl::Canadian(main, period, stones) => (main, period, stones),
+        };
+        self.send_checked(&format!("time_settings {} {} {}", main.as_secs(), period.as_secs(), stones));
+    }
+}
+
+impl Drop for GtpPlayer {
+    /// asks the engine to quit, killing it if it doesn't
+    fn drop(&mut self) {
+        if self.write("quit").and_then(|_| self.read_response(Some(QUIT_TIMEOUT))).is_err() {
+            let _ = self.process.kill();
+        }
+        let _ = self.process.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn responses_lose_their_prefix_and_id() {
+        assert_eq!(parse_response("= GNU Go\n"), Ok(String::from("GNU Go")));
+        assert_eq!(parse_response("=12 D4\n"), Ok(String::from("D4")));
+        assert_eq!(parse_response("?3 illegal move\n"), Err(String::from("illegal move")));
+        assert!(parse_response("D4\n").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn engines_that_exit_dont_start() {
+        // the engine can be gone before the name is sent, or after
+        let error = GtpPlayer::new("true").err().unwrap();
+        assert!(matches!(error.kind(), io::ErrorKind::UnexpectedEof | io::ErrorKind::BrokenPipe));
+    }
+
+    /// a 5x5 game where black has walled off the whole board and white is to play
+    fn lost_game() -> GameState {
+        let colours: Vec<Colour> = (0..25).map(|point| if point / 5 == 1 || point / 5 == 3 { Colour::Black } else { Colour::Empty }).collect();
+        let mut tree = GameTree::from_board(BoardState::from_colours(&colours, 5, Colour::White).unwrap());
+        tree.set_first_colour(Colour::White);
+        GameState::from_game_tree(tree)
+    }
+
+    #[test]
+    fn only_resigns_when_asked_to() {
+        let config = SearchConfig { max_iterations: 2000, threads: 1, seed: Some(1), resign_moves: 1, ..SearchConfig::new() };
+        let stop = AtomicBool::new(false);
+
+        let mut player = MctsPlayer::new(config);
+        assert!(!matches!(player.genmove(&lost_game(), &stop, &mut |_, _, _| ()), Turn::Resign));
+
+        let mut player = MctsPlayer::new(SearchConfig { resign_threshold: 0.3, ..config });
+        assert!(matches!(player.genmove(&lost_game(), &stop, &mut |_, _, _| ()), Turn::Resign));
+    }
+}
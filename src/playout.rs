@@ -0,0 +1,443 @@
+// a small mutable board for playing out random games quickly in the MCTS
+use rand::Rng;
+
+use crate::board_state::BoardState;
+use crate::colour::{Colour, Outcome};
+
+/// A board that is changed in place, unlike `BoardState`, for the random games of the MCTS
+///
+/// Groups are kept as a union-find where every stone points straight at the root of its group, and the
+/// smaller group is relabelled when two are joined. Liberties are pseudo-liberties (each stone and empty
+/// neighbour pair is counted) which are enough to tell when a group has none left. Only simple ko is checked.
+#[derive(Clone, Debug)]
+pub struct PlayoutBoard {
+    size: usize,
+    komi: f32,
+    colours: Vec<Colour>,
+    root: Vec<usize>, // the root stone of each stone's group
+    next: Vec<usize>, // the next stone of the same group, going round in a circle
+    stones: Vec<usize>, // the number of stones in each group, kept at the root
+    liberties: Vec<usize>, // the pseudo-liberties of each group, kept at the root
+    empty: Vec<usize>, // every empty point, in no particular order
+    empty_index: Vec<usize>, // where each empty point is in the empty list
+    ko: Option<usize>, // the point that can't be played as it would retake a ko
+}
+
+impl PlayoutBoard {
+    /// copies the stones, ko and komi of the board
+    pub fn from_board(board: &BoardState) -> Self {
+        let size = board.size;
+        let points = size * size;
+
+        let mut playout = PlayoutBoard {
+            size,
+            komi: board.rules.komi,
+            colours: vec![Colour::Empty; points],
+            root: (0..points).collect(),
+            next: (0..points).collect(),
+            stones: vec![0; points],
+            liberties: vec![0; points],
+            empty: (0..points).collect(),
+            empty_index: (0..points).collect(),
+            ko: None,
+        };
+
+        for (point, colour) in board.get_grid().into_iter().enumerate() {
+            if colour != Colour::Empty {
+                playout.place(point, colour); // a legal position has nothing to capture
+            }
+        }
+        playout.ko = board.zobrist_table.get_ko_point();
+        playout
+    }
+
+    /// returns the points next to the given point
+    fn neighbours(&self, point: usize) -> impl Iterator<Item = usize> {
+        let (x, y) = (point / self.size, point % self.size);
+        let mut neighbours = [0; 4];
+        let mut count = 0;
+
+        if x > 0 {
+            neighbours[count] = point - self.size;
+            count += 1;
+        }
+        if y > 0 {
+            neighbours[count] = point - 1;
+            count += 1;
+        }
+        if x + 1 < self.size {
+            neighbours[count] = point + self.size;
+            count += 1;
+        }
+        if y + 1 < self.size {
+            neighbours[count] = point + 1;
+            count += 1;
+        }
+        neighbours.into_iter().take(count)
+    }
+
+    /// returns how many of the point's neighbours belong to the group
+    fn count_edges(&self, point: usize, root: usize) -> usize {
+        self.neighbours(point).filter(|&neighbour| self.colours[neighbour] != Colour::Empty && self.root[neighbour] == root).count()
+    }
+
+    /// returns true if the colour can play at the point without suicide or retaking a ko
+    pub fn is_legal(&self, point: usize, colour: Colour) -> bool {
+        if self.colours[point] != Colour::Empty || self.ko == Some(point) {
+            return false;
+        }
+
+        self.neighbours(point).any(|neighbour| {
+            let neighbour_colour = self.colours[neighbour];
+            if neighbour_colour == Colour::Empty {
+                return true;
+            }
+
+            // the group has a liberty somewhere else, or it is the opponent's and gets captured
+            let root = self.root[neighbour];
+            let remaining = self.liberties[root] - self.count_edges(point, root);
+            (neighbour_colour == colour) == (remaining > 0)
+        })
+    }
+
+    /// returns true if every neighbour of the point is a stone of the colour, so it shouldn't be filled
+    pub fn is_eye(&self, point: usize, colour: Colour) -> bool {
+        self.neighbours(point).all(|neighbour| self.colours[neighbour] == colour)
+    }
+
+    /// Plays a stone if it is legal, capturing any groups left without liberties
+    pub fn play(&mut self, point: usize, colour: Colour) -> bool {
+        if !self.is_legal(point, colour) {
+            return false;
+        }
+
+        self.place(point, colour);
+
+        let mut captured = 0;
+        let mut captured_point = None;
+        for neighbour in self.neighbours(point) {
+            if self.colours[neighbour] == colour.swap_turn() && self.liberties[self.root[neighbour]] == 0 {
+                captured += self.remove_group(self.root[neighbour]);
+                captured_point = Some(neighbour);
+            }
+        }
+
+        // taking a single stone with a single stone leaves a ko that can't be retaken straight away
+        let root = self.root[point];
+        self.ko = if captured == 1 && self.stones[root] == 1 && self.liberties[root] == 1 { captured_point } else { None };
+        true
+    }
+
+    /// passes, which lets the opponent take back a ko
+    pub fn pass(&mut self) {
+        self.ko = None;
+    }
+
+    /// puts a stone down, joining it to the groups of its colour next to it
+    fn place(&mut self, point: usize, colour: Colour) {
+        self.colours[point] = colour;
+        self.remove_empty(point);
+        self.root[point] = point;
+        self.next[point] = point;
+        self.stones[point] = 1;
+        self.liberties[point] = 0;
+
+        for neighbour in self.neighbours(point) {
+            if self.colours[neighbour] == Colour::Empty {
+                self.liberties[point] += 1;
+            } else {
+                let root = self.root[neighbour];
+                self.liberties[root] -= 1;
+            }
+        }
+
+        for neighbour in self.neighbours(point) {
+            if self.colours[neighbour] == colour && self.root[neighbour] != self.root[point] {
+                self.join(self.root[point], self.root[neighbour]);
+            }
+        }
+    }
+
+    /// joins two groups, relabelling the stones of the smaller one
+    fn join(&mut self, first: usize, second: usize) {
+        let (root, other) = if self.stones[first] >= self.stones[second] { (first, second) } else { (second, first) };
+
+        let mut stone = other;
+        loop {
+            self.root[stone] = root;
+            stone = self.next[stone];
+            if stone == other {
+                break;
+            }
+        }
+
+        self.next.swap(root, other); // splices the two circles into one
+        self.stones[root] += self.stones[other];
+        self.liberties[root] += self.liberties[other];
+    }
+
+    /// takes a group off the board, returning how many stones it had
+    fn remove_group(&mut self, root: usize) -> usize {
+        let mut removed = Vec::with_capacity(self.stones[root]);
+        let mut stone = root;
+        loop {
+            removed.push(stone);
+            stone = self.next[stone];
+            if stone == root {
+                break;
+            }
+        }
+
+        for &stone in &removed {
+            self.colours[stone] = Colour::Empty;
+            self.empty_index[stone] = self.empty.len();
+            self.empty.push(stone);
+        }
+
+        // every group next to the removed stones gains them as liberties
+        for &stone in &removed {
+            for neighbour in self.neighbours(stone) {
+                if self.colours[neighbour] != Colour::Empty {
+                    let root = self.root[neighbour];
+                    self.liberties[root] += 1;
+                }
+            }
+        }
+        removed.len()
+    }
+
+    /// takes the point out of the empty list by swapping the last empty point into its place
+    fn remove_empty(&mut self, point: usize) {
+        let index = self.empty_index[point];
+        let last = self.empty.pop().unwrap();
+        if last != point {
+            self.empty[index] = last;
+            self.empty_index[last] = index;
+        }
+    }
+
+    /// Plays a random legal move for the colour that doesn't fill its own eye, returning None if there isn't one
+    ///
+    /// the empty points are tried from a random place in the list, so a move is usually found straight away
+    pub fn play_random_move(&mut self, colour: Colour, rng: &mut impl Rng) -> Option<usize> {
+        let count = self.empty.len();
+        if count == 0 {
+            return None;
+        }
+
+        let start = rng.gen_range(0..count);
+        for offset in 0..count {
+            let point = self.empty[(start + offset) % count];
+            if !self.is_eye(point, colour) && self.play(point, colour) {
+                return Some(point);
+            }
+        }
+        None
+    }
+
+    /// Plays random moves from the colour until both players pass in a row or the move limit is reached
+    ///
+    /// passes are how many passes in a row led to the position, so two means the game is already over.
+    /// returns the points played and who played them in order, for the AMAF statistics of the MCTS
+    pub fn play_random_game(&mut self, colour: Colour, passes: usize, rng: &mut impl Rng) -> Vec<(usize, Colour)> {
+        let max_moves = self.size * self.size * 3; // ko fights can go on for a long time
+        let mut colour = colour;
+        let mut passes = passes;
+        let mut moves = 0;
+        let mut played = Vec::new();
+
+        while passes < 2 && moves < max_moves {
+            match self.play_random_move(colour, rng) {
+                Some(point) => {
+                    played.push((point, colour));
+                    passes = 0;
+                }
+                None => {
+                    self.pass();
+                    passes += 1;
+                }
+            }
+            moves += 1;
+            colour = colour.swap_turn();
+        }
+        played
+    }
+
+    /// returns who each point belongs to: the colour of its stone, or of the stones around its empty area
+    pub fn get_ownership(&self) -> Vec<Colour> {
+        let mut owners = self.colours.clone();
+        let mut visited = vec![false; owners.len()];
+
+        for start in 0..owners.len() {
+            if self.colours[start] != Colour::Empty || visited[start] {
+                continue;
+            }
+
+            // flood the empty area, noting which colours border it
+            let mut area = vec![start];
+            let mut queue = vec![start];
+            let mut borders = [false, false];
+            visited[start] = true;
+
+            while let Some(point) = queue.pop() {
+                for neighbour in self.neighbours(point) {
+                    match self.colours[neighbour] {
+                        Colour::Empty if !visited[neighbour] => {
+                            visited[neighbour] = true;
+                            area.push(neighbour);
+                            queue.push(neighbour);
+                        }
+                        Colour::Empty => {}
+                        colour => borders[colour.into_usize() - 1] = true,
+                    }
+                }
+            }
+
+            let owner = match borders {
+                [true, false] => Colour::Black,
+                [false, true] => Colour::White,
+                _ => Colour::Empty, // dame
+            };
+            for point in area {
+                owners[point] = owner;
+            }
+        }
+        owners
+    }
+
+    /// scores the board by area with the komi, as the playout finishes with every dead stone captured
+    pub fn get_outcome(&self) -> Outcome {
+        let owners = self.get_ownership();
+        let black = owners.iter().filter(|&&owner| owner == Colour::Black).count() as f32;
+        let white = owners.iter().filter(|&&owner| owner == Colour::White).count() as f32 + self.komi;
+
+        if black > white {
+            Outcome::BlackWin
+        } else if white > black {
+            Outcome::WhiteWin
+        } else {
+            Outcome::Draw
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::Coordinate;
+    use crate::rules::{KoRule, Rules};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// returns the legal points for the colour on each board
+    fn legal_points(board: &BoardState, playout: &PlayoutBoard, colour: Colour) -> (Vec<usize>, Vec<usize>) {
+        let points = 0..board.size * board.size;
+        let legal = points.clone().filter(|&point| board.add_stone(Coordinate::Index(point, board.size), colour).is_ok()).collect();
+        (legal, points.filter(|&point| playout.is_legal(point, colour)).collect())
+    }
+
+    /// plays the same random moves on both boards, checking they agree on every legal move and stone along the way,
+    /// and that a copy of each position agrees too
+    fn check_agreement(size: usize, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut board = BoardState::new(size).unwrap();
+        board.rules = Rules { ko: KoRule::Simple, suicide_allowed: false, ..Rules::default() }; // the rules the playouts follow
+        let mut playout = PlayoutBoard::from_board(&board);
+        let mut colour = Colour::Black;
+
+        for _ in 0..size * size * 3 {
+            let (legal, playout_legal) = legal_points(&board, &playout, colour);
+            assert_eq!(playout_legal, legal);
+            assert_eq!(legal_points(&board, &PlayoutBoard::from_board(&board), colour).1, legal);
+
+            if legal.is_empty() {
+                break;
+            }
+            let point = legal[rng.gen_range(0..legal.len())];
+            board = board.add_stone(Coordinate::Index(point, size), colour).unwrap();
+            assert!(playout.play(point, colour));
+            assert_eq!(playout.colours, board.get_grid());
+
+            colour = colour.swap_turn();
+        }
+    }
+
+    #[test]
+    fn agrees_with_board_state_on_legal_moves() {
+        for seed in 0..8 {
+            check_agreement(5, seed);
+            check_agreement(7, seed);
+        }
+    }
+
+    #[test]
+    fn copies_a_ko_that_was_just_taken() {
+        let mut board = BoardState::new(5).unwrap();
+        board.rules = Rules { ko: KoRule::Simple, suicide_allowed: false, ..Rules::default() };
+        for (point, colour) in [(2, Colour::Black), (1, Colour::White), (8, Colour::Black), (5, Colour::White), (12, Colour::Black), (11, Colour::White), (24, Colour::Black), (7, Colour::White), (6, Colour::Black)] {
+            board = board.add_stone(Coordinate::Index(point, 5), colour).unwrap();
+        }
+
+        let playout = PlayoutBoard::from_board(&board);
+        let (legal, playout_legal) = legal_points(&board, &playout, Colour::White);
+        assert!(!legal.contains(&7));
+        assert_eq!(playout_legal, legal);
+
+        let passed = board.pass(Colour::White);
+        let (legal, playout_legal) = legal_points(&passed, &PlayoutBoard::from_board(&passed), Colour::Black);
+        assert_eq!(playout_legal, legal);
+    }
+
+    #[test]
+    fn copies_a_board_with_groups() {
+        let mut board = BoardState::new(5).unwrap();
+        for (point, colour) in [(0, Colour::Black), (1, Colour::Black), (5, Colour::White), (6, Colour::White)] {
+            board = board.add_stone(Coordinate::Index(point, 5), colour).unwrap();
+        }
+
+        // the black pair in the corner has a single liberty left, which white takes to capture both stones
+        let mut playout = PlayoutBoard::from_board(&board);
+        assert_eq!(playout.colours, board.get_grid());
+        assert!(playout.play(2, Colour::White));
+        assert_eq!(playout.colours, board.add_stone(Coordinate::Index(2, 5), Colour::White).unwrap().get_grid());
+        assert_eq!(playout.colours[0], Colour::Empty);
+        assert_eq!(playout.colours[1], Colour::Empty);
+    }
+
+    #[test]
+    fn simple_ko_cant_be_retaken_straight_away() {
+        // black takes the white stone on 7 with 6, leaving a ko white can only retake after a pass
+        let mut playout = PlayoutBoard::from_board(&BoardState::new(5).unwrap());
+        for (point, colour) in [(2, Colour::Black), (8, Colour::Black), (12, Colour::Black), (1, Colour::White), (5, Colour::White), (11, Colour::White), (7, Colour::White)] {
+            assert!(playout.play(point, colour));
+        }
+        assert!(playout.play(6, Colour::Black));
+        assert_eq!(playout.colours[7], Colour::Empty);
+
+        assert!(!playout.is_legal(7, Colour::White));
+        playout.pass();
+        assert!(playout.is_legal(7, Colour::White));
+    }
+
+    #[test]
+    fn equal_areas_are_a_draw() {
+        // a black and a white column either side of an empty one that neither owns
+        let colours: Vec<Colour> = (0..9).map(|point| match point % 3 {
+            0 => Colour::Black,
+            2 => Colour::White,
+            _ => Colour::Empty,
+        }).collect();
+        let mut board = BoardState::from_colours(&colours, 3, Colour::Black).unwrap();
+
+        board.rules.komi = 0.0;
+        assert_eq!(PlayoutBoard::from_board(&board).get_outcome(), Outcome::Draw);
+        board.rules.komi = 1.0;
+        assert_eq!(PlayoutBoard::from_board(&board).get_outcome(), Outcome::WhiteWin);
+        board.rules.komi = -1.0;
+        assert_eq!(PlayoutBoard::from_board(&board).get_outcome(), Outcome::BlackWin);
+
+        assert_eq!(Outcome::Draw.get_points(Colour::Black), Outcome::Draw.get_points(Colour::White));
+        assert_eq!(Outcome::BlackWin.get_points(Colour::Black), 2 * Outcome::Draw.get_points(Colour::Black));
+        assert_eq!(Outcome::BlackWin.get_points(Colour::White), 0);
+    }
+}
@@ -0,0 +1,104 @@
+// the rule sets that can be played under
+use crate::scoring::ScoringRule;
+
+pub const DEFAULT_KOMI: f32 = 0.5; // the default board is small so komi is much lower than usual
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KoRule {
+    Simple, // only the position before the opponent's last move can't be repeated
+    PositionalSuperko, // no position can be repeated
+    SituationalSuperko, // no position can be repeated with the same player to move
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rules {
+    pub ko: KoRule,
+    pub suicide_allowed: bool, // whether a move can capture its own group (of more than one stone)
+    pub komi: f32,
+    pub scoring: ScoringRule,
+}
+
+impl Rules {
+    /// area scoring, positional superko and suicide allowed
+    pub fn tromp_taylor() -> Self {
+        Rules {
+            ko: KoRule::PositionalSuperko,
+            suicide_allowed: true,
+            komi: 7.5,
+            scoring: ScoringRule::Area,
+        }
+    }
+
+    pub fn chinese() -> Self {
+        Rules {
+            ko: KoRule::PositionalSuperko,
+            suicide_allowed: false,
+            komi: 7.5,
+            scoring: ScoringRule::Area,
+        }
+    }
+
+    pub fn japanese() -> Self {
+        Rules {
+            ko: KoRule::Simple,
+            suicide_allowed: false,
+            komi: 6.5,
+            scoring: ScoringRule::Territory,
+        }
+    }
+
+    pub fn aga() -> Self {
+        Rules {
+            ko: KoRule::SituationalSuperko,
+            suicide_allowed: false,
+            komi: 7.5,
+            scoring: ScoringRule::Aga,
+        }
+    }
+
+    pub fn new_zealand() -> Self {
+        Rules {
+            ko: KoRule::SituationalSuperko,
+            suicide_allowed: true,
+            komi: 7.0,
+            scoring: ScoringRule::Area,
+        }
+    }
+
+    /// returns the preset with the given name, which is also how the sgf RU property is read
+    pub fn from_name(name: &str) -> Option<Rules> {
+        match name.to_lowercase().as_str() {
+            "tromp-taylor" | "tromp_taylor" | "tt" => Some(Rules::tromp_taylor()),
+            "chinese" | "cn" => Some(Rules::chinese()),
+            "japanese" | "korean" | "jp" => Some(Rules::japanese()),
+            "aga" => Some(Rules::aga()),
+            "new zealand" | "new_zealand" | "nz" => Some(Rules::new_zealand()),
+            _ => None,
+        }
+    }
+
+    /// returns the name of the preset these rules are based on (ignoring komi), for the sgf RU property
+    pub fn get_name(&self) -> String {
+        let presets = [
+            ("Tromp-Taylor", Rules::tromp_taylor()),
+            ("Chinese", Rules::chinese()),
+            ("Japanese", Rules::japanese()),
+            ("AGA", Rules::aga()),
+            ("NZ", Rules::new_zealand()),
+        ];
+
+        presets.iter()
+            .find(|(_, preset)| preset.ko == self.ko && preset.suicide_allowed == self.suicide_allowed && preset.scoring == self.scoring)
+            .map(|(name, _)| String::from(*name))
+            .unwrap_or_else(|| self.scoring.get_string())
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            komi: DEFAULT_KOMI,
+            ..Rules::chinese()
+        }
+    }
+}
@@ -0,0 +1,159 @@
+// the different ways of counting the score at the end of a game
+use std::fmt;
+
+use crate::board_state::BoardState;
+use crate::colour::Colour;
+use crate::group_state::GroupState;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScoringRule {
+    Area, // Chinese: territory + stones on the board
+    Territory, // Japanese/Korean: territory + prisoners
+    Aga, // territory + prisoners, where every pass gives the opponent a prisoner
+}
+
+impl ScoringRule {
+    pub fn get_string(&self) -> String {
+        match self {
+            ScoringRule::Area => String::from("Area"),
+            ScoringRule::Territory => String::from("Territory"),
+            ScoringRule::Aga => String::from("AGA"),
+        }
+    }
+
+    /// returns the next rule (used to cycle through them in the gui)
+    pub fn next(&self) -> ScoringRule {
+        match self {
+            ScoringRule::Area => ScoringRule::Territory,
+            ScoringRule::Territory => ScoringRule::Aga,
+            ScoringRule::Aga => ScoringRule::Area,
+        }
+    }
+}
+
+/// the points of a single colour
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ColourScore {
+    pub territory: usize, // empty points surrounded only by this colour
+    pub stones: usize, // stones on the board
+    pub captures: usize, // prisoners, including dead stones and (for AGA) pass stones
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ScoreBreakdown {
+    pub rule: ScoringRule,
+    pub black: ColourScore,
+    pub white: ColourScore,
+    pub komi: f32,
+}
+
+impl ScoreBreakdown {
+    /// Counts a finished board, which should already have its dead stones taken off.
+    ///
+    /// passes are how many times black and white passed, used for AGA pass stones
+    pub fn count(board: &BoardState, passes: [usize; 2]) -> Self {
+        let grid = board.get_grid();
+        let rule = board.rules.scoring;
+
+        let count_colour = |colour: Colour| {
+            let index = colour.into_usize() - 1;
+            let opponent_passes = passes[1 - index];
+
+            ColourScore {
+                territory: GroupState::combine_groups(&board.get_territory(&grid, colour)).len(),
+                stones: grid.iter().filter(|&&point| point == colour).count(),
+                captures: board.prisoners[index] + if rule == ScoringRule::Aga { opponent_passes } else { 0 },
+            }
+        };
+
+        ScoreBreakdown {
+            rule,
+            black: count_colour(Colour::Black),
+            white: count_colour(Colour::White),
+            komi: board.rules.komi,
+        }
+    }
+
+    /// returns the points of the given colour under the rule being used (without komi)
+    fn points(&self, score: &ColourScore) -> f32 {
+        match self.rule {
+            ScoringRule::Area => (score.territory + score.stones) as f32,
+            ScoringRule::Territory | ScoringRule::Aga => (score.territory + score.captures) as f32,
+        }
+    }
+
+    pub fn black_total(&self) -> f32 {
+        self.points(&self.black)
+    }
+
+    pub fn white_total(&self) -> f32 {
+        self.points(&self.white) + self.komi
+    }
+
+    /// returns the winner and the margin they won by, the winner is Empty for a draw (only possible with integer komi)
+    pub fn get_winner(&self) -> (Colour, f32) {
+        let (black, white) = (self.black_total(), self.white_total());
+
+        if black > white {
+            (Colour::Black, black - white)
+        } else if white > black {
+            (Colour::White, white - black)
+        } else {
+            (Colour::Empty, 0.0)
+        }
+    }
+
+    /// returns the result in sgf and gtp form ie "B+3" or "W+0.5", or "0" for a draw
+    pub fn get_result(&self) -> String {
+        match self.get_winner() {
+            (Colour::Black, margin) => format!("B+{}", margin),
+            (Colour::White, margin) => format!("W+{}", margin),
+            _ => String::from("0"),
+        }
+    }
+}
+
+impl fmt::Display for ScoreBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counted = match self.rule {
+            ScoringRule::Area => "stones",
+            _ => "captures",
+        };
+        let (black_counted, white_counted) = match self.rule {
+            ScoringRule::Area => (self.black.stones, self.white.stones),
+            _ => (self.black.captures, self.white.captures),
+        };
+
+        writeln!(f, "{} scoring", self.rule.get_string())?;
+        writeln!(f, "Black: {} territory + {} {} = {}", self.black.territory, black_counted, counted, self.black_total())?;
+        write!(f, "White: {} territory + {} {} + {} komi = {}", self.white.territory, white_counted, counted, self.komi, self.white_total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::Coordinate;
+    use crate::rules::Rules;
+
+    #[test]
+    fn equal_totals_are_a_draw() {
+        // black's stone on a 2x2 board is worth 4 points, which integer komi can match
+        let mut board = BoardState::new(2).unwrap();
+        board.rules = Rules { komi: 4.0, ..Rules::new_zealand() };
+        let board = board.add_stone(Coordinate::Index(0, 2), Colour::Black).unwrap();
+
+        let score = ScoreBreakdown::count(&board, [0, 0]);
+        assert_eq!(score.get_winner(), (Colour::Empty, 0.0));
+        assert_eq!(score.get_result(), "0");
+    }
+
+    #[test]
+    fn results_name_the_winner() {
+        let mut board = BoardState::new(2).unwrap();
+        board.rules = Rules { komi: 3.5, ..Rules::chinese() };
+        let board = board.add_stone(Coordinate::Index(0, 2), Colour::Black).unwrap();
+
+        assert_eq!(ScoreBreakdown::count(&board, [0, 0]).get_result(), "B+0.5");
+    }
+}
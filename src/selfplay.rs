@@ -0,0 +1,215 @@
+// plays games between two engines without the gui and records how they went
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+use crate::cli::Options;
+use go_rs::colour::Colour;
+use go_rs::game_state::GameState;
+use go_rs::player::{Player, PlayerKind};
+use go_rs::turn::Turn;
+
+/// how a single game went
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    pub black: usize, // which engine played black
+    pub winner: Colour, // Empty for a draw
+    pub margin: Option<f32>, // None for resignations and forfeits
+    pub forfeited: bool, // the loser tried to play an illegal move
+    pub result: String,
+    pub turns: usize,
+    pub move_times: [(Duration, usize); 2], // total time and number of turns taken by each engine
+}
+
+/// the results of every game played between two engines
+pub struct Statistics {
+    pub engines: [String; 2], // the names of the engines
+    pub games: Vec<GameRecord>,
+}
+
+impl Statistics {
+    /// returns the number of games won by each engine
+    pub fn wins_by_engine(&self) -> [usize; 2] {
+        let mut wins = [0, 0];
+        for game in self.games.iter().filter(|game| game.winner != Colour::Empty) {
+            let winner = if game.winner == Colour::Black { game.black } else { 1 - game.black };
+            wins[winner] += 1;
+        }
+        wins
+    }
+
+    /// returns the number of games won by black and white
+    pub fn wins_by_colour(&self) -> [usize; 2] {
+        let mut wins = [0, 0];
+        for game in self.games.iter().filter(|game| game.winner != Colour::Empty) {
+            wins[game.winner.into_usize() - 1] += 1;
+        }
+        wins
+    }
+
+    /// returns the number of games that ended in a draw
+    pub fn draws(&self) -> usize {
+        self.games.iter().filter(|game| game.winner == Colour::Empty).count()
+    }
+
+    /// returns the average time each engine took per turn
+    pub fn time_per_move(&self) -> [Duration; 2] {
+        [0, 1].map(|engine| {
+            let (total, turns) = self.games.iter()
+                .map(|game| game.move_times[engine])
+                .fold((Duration::ZERO, 0), |acc, (time, turns)| (acc.0 + time, acc.1 + turns));
+            total / turns.max(1) as u32
+        })
+    }
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let games = self.games.len().max(1) as f32;
+        let by_engine = self.wins_by_engine();
+        let by_colour = self.wins_by_colour();
+        let times = self.time_per_move();
+        let margins: Vec<f32> = self.games.iter().filter_map(|game| game.margin).collect();
+        let average_margin = margins.iter().sum::<f32>() / margins.len().max(1) as f32;
+        let average_length = self.games.iter().map(|game| game.turns).sum::<usize>() as f32 / games;
+
+        writeln!(f, "{} games", self.games.len())?;
+        for (index, engine) in self.engines.iter().enumerate() {
+            writeln!(f, "Engine {}: {}", index + 1, engine)?;
+            writeln!(f, "    won {} ({:.1}%), {:.3}s per move", by_engine[index], by_engine[index] as f32 * 100.0 / games, times[index].as_secs_f32())?;
+        }
+        writeln!(f, "Black won {}, White won {}, {} drawn", by_colour[0], by_colour[1], self.draws())?;
+        let forfeits = self.games.iter().filter(|game| game.forfeited).count();
+        writeln!(f, "Average margin {:.1} ({} resignations, {} forfeits), average length {:.1} turns", average_margin, self.games.len() - margins.len() - forfeits, forfeits, average_length)?;
+
+        for (number, game) in self.games.iter().enumerate() {
+            write!(f, "\nGame {}: engine {} as black, {} after {} turns", number + 1, game.black + 1, game.result, game.turns)?;
+        }
+        Ok(())
+    }
+}
+
+/// Plays one game between the engines, returning the finished game and how it went
+fn play_game(options: &Options, players: &mut [Box<dyn Player>; 2], black: usize) -> (GameState, GameRecord) {
+    let mut game = options.new_game();
+    let max_turns = options.size * options.size * 4; // in case neither engine passes
+    let mut move_times = [(Duration::ZERO, 0); 2];
+    let mut forfeit = None; // the colour of an engine that tried to play an illegal move
+
+    game.game_tree.info.black = players[black].get_name();
+    game.game_tree.info.white = players[1 - black].get_name();
+    for player in players.iter_mut() {
+        player.new_game(&game);
+    }
+
+    while !game.check_end() && game.game_tree.get_pointer() < max_turns {
+        let engine = if game.turn == Colour::Black { black } else { 1 - black };
+        let colour = game.turn;
+
+        let start = Instant::now();
+        let turn = players[engine].genmove(&game, &AtomicBool::new(false), &mut |_, _, _| {});
+        move_times[engine].0 += start.elapsed();
+        move_times[engine].1 += 1;
+
+        if !game.play_turn(turn) { // an illegal move loses the game rather than being played as something else
+            eprintln!("{} played an illegal move ({:?}) as {} and forfeits the game", players[engine].get_name(), turn, colour.get_string());
+            forfeit = Some(colour);
+            break;
+        }
+        players[1 - engine].notify(turn, colour);
+    }
+
+    let (winner, margin) = game.calculate_total_completed_score();
+    let resigned = matches!(game.game_tree.get_current_node().turn, Turn::Resign);
+    let (winner, result) = match forfeit {
+        Some(colour) => {
+            let result = format!("{}+F", if colour == Colour::Black { "W" } else { "B" });
+            game.game_tree.info.result = Some(result.clone());
+            (colour.swap_turn(), result)
+        }
+        None => {
+            let result = game.get_result().unwrap_or_else(|| format!("{} (unfinished)", game.score().get_result()));
+            (if resigned { game.game_tree.get_last_colour().swap_turn() } else { winner }, result)
+        }
+    };
+
+    let record = GameRecord {
+        black,
+        winner,
+        margin: (!resigned && forfeit.is_none()).then_some(margin),
+        forfeited: forfeit.is_some(),
+        result,
+        turns: game.game_tree.get_pointer(),
+        move_times,
+    };
+    (game, record)
+}
+
+/// Plays the given number of games between the engines, swapping colours each game (unless a colour is fixed)
+///
+/// every game is written to the output directory as an sgf file, along with a summary of the results
+pub fn run(options: &Options, engines: [&PlayerKind; 2], output: &Path) -> io::Result<Statistics> {
+    let create = |kind: &PlayerKind| kind.create()?.ok_or(io::Error::new(io::ErrorKind::InvalidInput, "a human can't play in selfplay"));
+    let mut players = [create(engines[0])?, create(engines[1])?];
+
+    std::fs::create_dir_all(output)?;
+    let mut statistics = Statistics { engines: [players[0].get_name(), players[1].get_name()], games: Vec::new() };
+
+    for number in 1..=options.games {
+        let black = match options.bot_colour { // the first engine is "the bot"
+            Some(Colour::Black) => 0,
+            Some(_) => 1,
+            None => (number - 1) % 2,
+        };
+
+        let (mut game, record) = play_game(options, &mut players, black);
+        println!("Game {}: {} after {} turns", number, record.result, record.turns);
+
+        std::fs::write(output.join(format!("game_{:03}.sgf", number)), game.export_sgf())?;
+        statistics.games.push(record);
+    }
+
+    std::fs::write(output.join("summary.txt"), format!("{}\n", statistics))?;
+    Ok(statistics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use go_rs::coordinate::Coordinate;
+    use go_rs::player::RandomPlayer;
+
+    /// always plays on the first point, which is illegal once it has a stone
+    struct FirstPoint;
+
+    impl Player for FirstPoint {
+        fn get_name(&self) -> String {
+            String::from("first point")
+        }
+
+        fn genmove(&mut self, game: &GameState, _stop: &AtomicBool, _progress: &mut go_rs::player::Progress<'_>) -> Turn {
+            Turn::Move(Coordinate::Index(0, game.size))
+        }
+    }
+
+    #[test]
+    fn an_illegal_move_forfeits_the_game() {
+        let mut options = Options::new(5);
+        options.search.seed = Some(1);
+        let mut players: [Box<dyn Player>; 2] = [Box::new(FirstPoint), Box::new(RandomPlayer::new(Some(1)))];
+
+        let (mut game, record) = play_game(&options, &mut players, 0);
+        assert_eq!(record.winner, Colour::White);
+        assert!(record.forfeited);
+        assert_eq!(record.margin, None);
+        assert_eq!(record.result, "W+F");
+        assert_eq!(record.turns, 2);
+        assert!(game.export_sgf().contains("RE[W+F]"));
+
+        let statistics = Statistics { engines: [String::from("a"), String::from("b")], games: vec![record] };
+        assert_eq!(statistics.wins_by_engine(), [0, 1]);
+        assert!(statistics.to_string().contains("(0 resignations, 1 forfeits)"));
+    }
+}
@@ -0,0 +1,430 @@
+// reading and writing games in the Smart Game Format (FF[4])
+use crate::board_state::BoardState;
+use crate::colour::Colour;
+use crate::coordinate::Coordinate;
+use crate::fails::SgfErrors;
+use crate::rules::Rules;
+use crate::tree::GameTree;
+use crate::turn::Turn;
+
+/// a single node of an sgf file, ie `;B[dd]C[a comment]`
+#[derive(Clone, Debug)]
+pub struct SgfNode {
+    pub properties: Vec<(String, Vec<String>)>,
+    pub children: Vec<SgfNode>, // the first child is the main line
+}
+
+impl SgfNode {
+    /// returns the first value of the given property
+    pub fn get(&self, identifier: &str) -> Option<&String> {
+        self.get_all(identifier).and_then(|values| values.first())
+    }
+
+    /// returns every value of the given property
+    pub fn get_all(&self, identifier: &str) -> Option<&Vec<String>> {
+        self.properties.iter().find(|(id, _)| id == identifier).map(|(_, values)| values)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&mut self) -> Option<char> {
+        while self.position < self.chars.len() && self.chars[self.position].is_whitespace() {
+            self.position += 1;
+        }
+        self.chars.get(self.position).copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SgfErrors> {
+        if self.peek() != Some(expected) {
+            return Err(SgfErrors::InvalidSyntax(self.position));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    /// GameTree = "(" Sequence { GameTree } ")"
+    fn parse_game_tree(&mut self) -> Result<SgfNode, SgfErrors> {
+        self.expect('(')?;
+
+        let mut sequence = Vec::new();
+        while self.peek() == Some(';') {
+            sequence.push(self.parse_node()?);
+        }
+
+        let mut variations = Vec::new();
+        while self.peek() == Some('(') {
+            variations.push(self.parse_game_tree()?);
+        }
+        self.expect(')')?;
+
+        // link the sequence together so that each node points to the next
+        let mut node = sequence.pop().ok_or(SgfErrors::InvalidSyntax(self.position))?;
+        node.children = variations;
+
+        while let Some(mut parent) = sequence.pop() {
+            parent.children = vec![node];
+            node = parent;
+        }
+        Ok(node)
+    }
+
+    /// Node = ";" { Property }
+    fn parse_node(&mut self) -> Result<SgfNode, SgfErrors> {
+        self.expect(';')?;
+        let mut properties = Vec::new();
+
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_alphabetic() {
+                break;
+            }
+
+            let mut identifier = String::new();
+            while let Some(&c) = self.chars.get(self.position) {
+                if !c.is_ascii_alphabetic() {
+                    break;
+                }
+                if c.is_ascii_uppercase() { // old FF[3] files can have lowercase letters in identifiers
+                    identifier.push(c);
+                }
+                self.position += 1;
+            }
+
+            let mut values = Vec::new();
+            while self.peek() == Some('[') {
+                values.push(self.parse_value()?);
+            }
+
+            if values.is_empty() {
+                return Err(SgfErrors::InvalidSyntax(self.position));
+            }
+            properties.push((identifier, values));
+        }
+
+        Ok(SgfNode { properties, children: Vec::new() })
+    }
+
+    /// reads a "[...]" value, handling escaped characters
+    fn parse_value(&mut self) -> Result<String, SgfErrors> {
+        self.expect('[')?;
+        let mut value = String::new();
+
+        loop {
+            let c = *self.chars.get(self.position).ok_or(SgfErrors::InvalidSyntax(self.position))?;
+            self.position += 1;
+
+            match c {
+                ']' => break,
+                '\\' => {
+                    let escaped = *self.chars.get(self.position).ok_or(SgfErrors::InvalidSyntax(self.position))?;
+                    self.position += 1;
+                    if escaped != '\n' { // an escaped newline is a soft line break
+                        value.push(escaped);
+                    }
+                }
+                _ => value.push(c),
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+/// parses the first game in the given sgf text into a tree of nodes
+pub fn parse(text: &str) -> Result<SgfNode, SgfErrors> {
+    let mut parser = Parser { chars: text.chars().collect(), position: 0 };
+    parser.parse_game_tree()
+}
+
+/// converts an sgf point (ie "cd") into a coordinate
+pub fn point_to_coordinate(point: &str, size: usize) -> Result<Coordinate, SgfErrors> {
+    let letters: Vec<usize> = point.bytes().map(|b| b.wrapping_sub(b'a') as usize).collect();
+
+    if letters.len() != 2 || letters[0] >= size || letters[1] >= size {
+        return Err(SgfErrors::InvalidCoordinate(point.to_string()));
+    }
+
+    // sgf points are column then row, positions are row then column
+    Ok(Coordinate::Position((letters[1], letters[0]), size))
+}
+
+/// converts a coordinate into an sgf point
+pub fn coordinate_to_point(coordinate: Coordinate) -> String {
+    let (x, y) = coordinate.get_position();
+    format!("{}{}", (b'a' + y as u8) as char, (b'a' + x as u8) as char)
+}
+
+/// converts a move value into a turn, "" (or "tt" on small boards) is a pass
+fn value_to_turn(value: &str, size: usize) -> Result<Turn, SgfErrors> {
+    if value.is_empty() || (value == "tt" && size <= 19) {
+        return Ok(Turn::Pass);
+    }
+    Ok(Turn::Move(point_to_coordinate(value, size)?))
+}
+
+/// expands a point list, which may contain compressed rectangles ie "aa:cc"
+fn expand_points(values: &[String], size: usize) -> Result<Vec<Coordinate>, SgfErrors> {
+    let mut points = Vec::new();
+
+    for value in values {
+        if let Some((first, second)) = value.split_once(':') {
+            let (x1, y1) = point_to_coordinate(first, size)?.get_position();
+            let (x2, y2) = point_to_coordinate(second, size)?.get_position();
+
+            for x in x1.min(x2)..=x1.max(x2) {
+                for y in y1.min(y2)..=y1.max(y2) {
+                    points.push(Coordinate::Position((x, y), size));
+                }
+            }
+        } else {
+            points.push(point_to_coordinate(value, size)?);
+        }
+    }
+    Ok(points)
+}
+
+/// escapes the characters that would end a value early
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(']', "\\]")
+}
+
+/// Reads an sgf game into a GameTree, keeping every variation in the file.
+pub fn load_game(text: &str) -> Result<GameTree, SgfErrors> {
+    let root = parse(text)?;
+
+    if root.get("GM").is_some_and(|game| game != "1") {
+        return Err(SgfErrors::UnsupportedGame);
+    }
+
+    let size = match root.get("SZ") {
+        Some(value) => value.split(':').next().unwrap_or("").trim().parse().map_err(|_| SgfErrors::UnsupportedGame)?,
+        None => 19,
+    };
+
+    // unknown rules are played with the defaults, but keep the komi of the file
+    let mut rules = root.get("RU").and_then(|rules| Rules::from_name(rules.trim())).unwrap_or_default();
+    if let Some(komi) = root.get("KM").and_then(|komi| komi.trim().parse().ok()) {
+        rules.komi = komi;
+    }
+
+    let handicap = root.get("HA").and_then(|handicap| handicap.trim().parse().ok()).unwrap_or(0);
+
+    // white plays first after handicap stones unless the file says otherwise
+    let first_colour = match root.get("PL").map(|colour| colour.trim().to_uppercase()).as_deref() {
+        Some("W") => Colour::White,
+        Some("B") => Colour::Black,
+        _ if handicap >= 2 => Colour::White,
+        _ => Colour::Black,
+    };
+
+    // setup stones make up the starting position rather than being played
+    let mut stones = Vec::new();
+    for (property, colour) in [("AB", Colour::Black), ("AW", Colour::White)] {
+        if let Some(values) = root.get_all(property) {
+            stones.extend(expand_points(values, size)?.into_iter().map(|coordinate| (coordinate, colour)));
+        }
+    }
+
+    let mut board = BoardState::new(size).map_err(|_| SgfErrors::UnsupportedGame)?;
+    board.rules = rules;
+    let board = board.setup_stones(&stones, first_colour).map_err(SgfErrors::IllegalMove)?;
+
+    let mut tree = GameTree::from_board(board);
+    tree.set_first_colour(first_colour);
+    let info = &mut tree.info;
+
+    if let Some(black) = root.get("PB") {
+        info.black = black.clone();
+    }
+    if let Some(white) = root.get("PW") {
+        info.white = white.clone();
+    }
+    info.result = root.get("RE").cloned();
+    info.handicap = handicap;
+
+    // the root can also contain a move
+    add_variations(&mut tree, &root, size)?;
+    tree.select_main_line();
+
+    Ok(tree)
+}
+
+/// adds the move of the given node after the current node of the tree, followed by every variation after it
+fn add_variations(tree: &mut GameTree, node: &SgfNode, size: usize) -> Result<(), SgfErrors> {
+    for (property, colour) in [("B", Colour::Black), ("W", Colour::White)] {
+        if let Some(value) = node.get(property) {
+            let turn = value_to_turn(value, size)?;
+            let board = match turn {
+                Turn::Move(coordinate) => tree.get_current_board().add_stone(coordinate, colour).map_err(SgfErrors::IllegalMove)?,
+                _ => tree.get_current_board().clone(),
+            };
+            tree.add_move(turn, colour, board);
+        }
+    }
+
+    let current = tree.get_current_id();
+    for child in &node.children {
+        let _ = tree.jump(current);
+        add_variations(tree, child, size)?;
+    }
+    Ok(())
+}
+
+/// writes the move of the given node followed by every variation after it
+fn write_variations(tree: &GameTree, id: usize, sgf: &mut String) {
+    let node = tree.get_node(id);
+    let property = if node.colour == Colour::Black { "B" } else { "W" };
+
+    match node.turn {
+        Turn::Move(coordinate) => sgf.push_str(&format!(";{}[{}]", property, coordinate_to_point(coordinate))),
+        Turn::Pass => sgf.push_str(&format!(";{}[]", property)),
+        Turn::Resign => return, // resignations are only recorded in the result
+    }
+
+    write_children(tree, &node.children, sgf);
+}
+
+/// writes a single child as a continuation of the sequence, or several children as separate variations
+fn write_children(tree: &GameTree, children: &[usize], sgf: &mut String) {
+    let children: Vec<usize> = children.iter().copied().filter(|&child| tree.get_node(child).turn != Turn::Resign).collect();
+
+    if children.len() == 1 {
+        write_variations(tree, children[0], sgf);
+        return;
+    }
+
+    for child in children {
+        sgf.push_str("\n(");
+        write_variations(tree, child, sgf);
+        sgf.push(')');
+    }
+}
+
+/// Writes a GameTree as an sgf game.
+pub fn save_game(tree: &GameTree) -> String {
+    let root_board = &tree.get_root().board;
+    let info = &tree.info;
+    let mut sgf = format!("(;GM[1]FF[4]CA[UTF-8]AP[go-rs]SZ[{}]KM[{}]RU[{}]PB[{}]PW[{}]", root_board.size, root_board.rules.komi, root_board.rules.get_name(), escape(&info.black), escape(&info.white));
+
+    if let Some(result) = &info.result {
+        sgf.push_str(&format!("RE[{}]", escape(result)));
+    }
+    if info.handicap > 0 {
+        sgf.push_str(&format!("HA[{}]", info.handicap));
+    }
+    if tree.get_first_colour() == Colour::White {
+        sgf.push_str("PL[W]");
+    }
+
+    // setup stones
+    let grid = root_board.get_grid();
+    for (property, colour) in [("AB", Colour::Black), ("AW", Colour::White)] {
+        let points: Vec<String> = grid.iter()
+            .enumerate()
+            .filter(|(_, &point)| point == colour)
+            .map(|(index, _)| format!("[{}]", coordinate_to_point(Coordinate::Index(index, root_board.size))))
+            .collect();
+
+        if !points.is_empty() {
+            sgf.push_str(property);
+            sgf.push_str(&points.concat());
+        }
+    }
+    sgf.push('\n');
+
+    write_children(tree, &tree.get_root().children, &mut sgf);
+
+    sgf.push_str(")\n");
+    sgf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fails::TurnErrors;
+    use crate::rules::KoRule;
+
+    // setup stones, white to play, a pass and two variations after the first move
+    const GAME: &str = "(;GM[1]FF[4]SZ[5]KM[6.5]PB[Black]PW[White]PL[W]AB[aa][bb]AW[ee];W[cc](;B[dd];W[];B[])(;B[cd]C[the other line]))";
+
+    #[test]
+    fn parses_properties_and_variations() {
+        let root = parse(GAME).unwrap();
+        assert_eq!(root.get_all("AB").unwrap(), &vec![String::from("aa"), String::from("bb")]);
+        assert_eq!(root.get("PL").unwrap(), "W");
+
+        let first = &root.children[0];
+        assert_eq!(first.get("W").unwrap(), "cc");
+        assert_eq!(first.children.len(), 2);
+        assert_eq!(first.children[1].get("C").unwrap(), "the other line");
+    }
+
+    #[test]
+    fn loads_setup_stones_passes_and_variations() {
+        let mut tree = load_game(GAME).unwrap();
+        let size = 5;
+
+        let grid = tree.get_root().board.get_grid();
+        assert_eq!(grid[Coordinate::Position((0, 0), size).get_index()], Colour::Black);
+        assert_eq!(grid[Coordinate::Position((1, 1), size).get_index()], Colour::Black);
+        assert_eq!(grid[Coordinate::Position((4, 4), size).get_index()], Colour::White);
+        assert_eq!(tree.get_first_colour(), Colour::White);
+
+        // the main line ends with both players passing
+        while tree.move_forward().is_ok() {}
+        assert_eq!(tree.get_pointer(), 4);
+        assert_eq!(tree.get_current_node().turn, Turn::Pass);
+        assert!(tree.check_end());
+
+        // and the move after white's has a second variation
+        tree.jump(0).ok();
+        tree.move_forward().ok();
+        assert_eq!(tree.get_current_node().children.len(), 2);
+        tree.move_forward().ok();
+        tree.change_variation(true).ok();
+        assert_eq!(tree.get_current_node().turn, Turn::Move(Coordinate::Position((3, 2), size)));
+    }
+
+    #[test]
+    fn saved_games_load_back_the_same() {
+        let saved = save_game(&load_game(GAME).unwrap());
+        let reloaded = load_game(&saved).unwrap();
+
+        assert_eq!(save_game(&reloaded), saved);
+        assert_eq!(reloaded.get_root().board.get_grid(), load_game(GAME).unwrap().get_root().board.get_grid());
+        assert!(saved.contains("AB[aa][bb]"));
+        assert!(saved.contains("AW[ee]"));
+        assert!(saved.contains(";W[];B[]"));
+        assert!(saved.contains("PL[W]"));
+    }
+
+    #[test]
+    fn setup_positions_leave_no_history_behind() {
+        // a ko set up with white to play, under situational superko
+        let tree = load_game("(;GM[1]SZ[5]RU[NZ]PL[W]AB[ba][ab][bc]AW[ca][db][cc][bb])").unwrap();
+        let size = 5;
+        let mut board = tree.get_root().board.clone();
+        assert_eq!(board.rules.ko, KoRule::SituationalSuperko);
+        assert_eq!(board.zobrist_table.get_to_move(), Colour::White);
+
+        // black takes the ko after an exchange, and white can only take it back after another
+        board = board.add_stone(Coordinate::Position((4, 4), size), Colour::White).unwrap();
+        board = board.add_stone(Coordinate::Position((1, 2), size), Colour::Black).unwrap();
+        assert!(matches!(board.add_stone(Coordinate::Position((1, 1), size), Colour::White), Err(TurnErrors::Ko)));
+        board = board.add_stone(Coordinate::Position((4, 0), size), Colour::White).unwrap();
+        board = board.add_stone(Coordinate::Position((0, 4), size), Colour::Black).unwrap();
+        board = board.add_stone(Coordinate::Position((1, 1), size), Colour::White).unwrap();
+        assert_eq!(board.get_grid()[Coordinate::Position((1, 2), size).get_index()], Colour::Empty);
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        assert!(parse("(;GM[1]SZ[5];B[aa]").is_err());
+        assert!(matches!(load_game("(;GM[1]SZ[5];B[zz])"), Err(SgfErrors::InvalidCoordinate(_))));
+        assert!(matches!(load_game("(;GM[2])"), Err(SgfErrors::UnsupportedGame)));
+    }
+}
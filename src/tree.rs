@@ -1,131 +1,495 @@
 use crate::board_state::BoardState;
 use crate::colour::Colour;
 use crate::coordinate::Coordinate;
-use crate::fails::TreeErrors;
+use crate::fails::{BoardErrors, TreeErrors};
+use crate::rules::Rules;
 use crate::turn::Turn;
 
+/// information about a game that isn't part of the moves themselves (used for SGF)
+#[derive(Clone, Debug)]
+pub struct GameInfo {
+    pub black: String, // name of the black player
+    pub white: String, // name of the white player
+    pub result: Option<String>, // in SGF form ie "B+R" or "W+3.5"
+    pub handicap: usize, // the number of handicap stones black started with
+}
+
+impl GameInfo {
+    pub fn new() -> Self {
+        GameInfo {
+            black: String::from("Black"),
+            white: String::from("White"),
+            result: None,
+            handicap: 0,
+        }
+    }
+}
+
+impl Default for GameInfo {
+    fn default() -> Self {
+        GameInfo::new()
+    }
+}
+
+/// a single position in the game tree
+#[derive(Clone)]
+pub struct GameNode {
+    pub turn: Turn, // the turn that led to this node
+    pub colour: Colour, // the colour that played the turn (Empty for the root)
+    pub board: BoardState,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>, // the first child is the main line
+    selected: usize, // the child followed when moving forward
+}
+
+#[derive(Clone)]
 pub struct GameTree {
-    board_states: Vec<(Turn, BoardState)>,
-    pointer: usize,
+    nodes: Vec<GameNode>, // where each index is the id of the node
+    pointer: usize, // the id of the node currently being looked at
+    first_colour: Colour, // the colour to play from the root, which is white after handicap stones
+    pub info: GameInfo,
 }
 
 
 impl GameTree {
-    pub fn new(size: usize) -> Self {
-        let mut board_states = Vec::new();
-        board_states.push((Turn::Pass, BoardState::new(size)));
+    pub fn new(size: usize) -> Result<Self, BoardErrors> {
+        Ok(GameTree::from_board(BoardState::new(size)?))
+    }
+
+    /// creates a tree starting from the given position (ie with setup stones already placed)
+    pub fn from_board(board: BoardState) -> Self {
+        let root = GameNode {
+            turn: Turn::Pass,
+            colour: Colour::Empty,
+            board,
+            parent: None,
+            children: Vec::new(),
+            selected: 0,
+        };
 
         GameTree {
-            board_states,
+            nodes: vec![root],
             pointer: 0,
+            first_colour: Colour::Black,
+            info: GameInfo::new(),
         }   
     }
 
-    pub fn move_back(&mut self) -> Result<&(Turn, BoardState), TreeErrors> {
-        if self.pointer == 0 {
+    /// moves to the parent of the current node
+    pub fn move_back(&mut self) -> Result<&GameNode, TreeErrors> {
+        let Some(parent) = self.nodes[self.pointer].parent else {
             return Err(TreeErrors::BelowZero);
-        }
-        
-        self.pointer -= 1;
+        };
 
-        Ok(&self.board_states[self.pointer])
+        self.pointer = parent;
+        Ok(&self.nodes[self.pointer])
     }
 
-    pub fn move_forward(&mut self) -> Result<&(Turn, BoardState), TreeErrors> {
-        if self.pointer == self.board_states.len() - 1 {
+    /// moves to the selected child of the current node
+    pub fn move_forward(&mut self) -> Result<&GameNode, TreeErrors> {
+        let node = &self.nodes[self.pointer];
+        let Some(&child) = node.children.get(node.selected) else {
             return Err(TreeErrors::AboveMax);
-        }
+        };
 
-        self.pointer += 1;
+        self.pointer = child;
+        Ok(&self.nodes[self.pointer])
+    }
+
+    /// moves to the next (or previous) variation of the current node, ie another move played from the same position
+    pub fn change_variation(&mut self, forward: bool) -> Result<&GameNode, TreeErrors> {
+        let Some(parent) = self.nodes[self.pointer].parent else {
+            return Err(TreeErrors::NoVariation);
+        };
+
+        let siblings = &self.nodes[parent].children;
+        let position = siblings.iter().position(|&id| id == self.pointer).unwrap();
+        let new_position = if forward {
+            position + 1
+        } else {
+            position.checked_sub(1).ok_or(TreeErrors::NoVariation)?
+        };
 
-        Ok(&self.board_states[self.pointer])
+        let Some(&sibling) = siblings.get(new_position) else {
+            return Err(TreeErrors::NoVariation);
+        };
+
+        self.nodes[parent].selected = new_position;
+        self.pointer = sibling;
+        Ok(&self.nodes[self.pointer])
     }
 
-    
-    pub fn _jump(&mut self, index: usize) -> Result<&(Turn, BoardState), TreeErrors> {
-        if index >= self.board_states.len() {
+    /// moves to the given node
+    pub fn jump(&mut self, id: usize) -> Result<&GameNode, TreeErrors> {
+        if id >= self.nodes.len() {
             return Err(TreeErrors::AboveMax);
         }
 
-        self.pointer = index;
-        return Ok(&self.board_states[self.pointer]);
+        self.pointer = id;
+        Ok(&self.nodes[self.pointer])
     }
 
-    pub fn _latest(&mut self) -> &(Turn, BoardState) {
-        self.pointer = self.board_states.len() - 1;
-
-        return &self.board_states[self.pointer];
+    /// moves to the end of the current line
+    pub fn _latest(&mut self) -> &GameNode {
+        self.reset();
+        &self.nodes[self.pointer]
     }
 
+    /// moves to the end of the current line
     pub fn reset(&mut self) {
-        self.pointer = self.board_states.len() - 1;
+        while self.move_forward().is_ok() {}
     }
 
-    pub fn add_move(&mut self, turn: Turn, board: BoardState) {
+    /// selects the first variation at every node and moves to the end of the main line
+    pub fn select_main_line(&mut self) {
+        for node in &mut self.nodes {
+            node.selected = 0;
+        }
+        self.pointer = 0;
         self.reset();
-        self.board_states.push((turn, board));
-        self.pointer += 1;
     }
 
+    /// adds the turn played by the given colour from the current node and moves to it
+    /// 
+    /// if the turn has already been played from this position the existing variation is reused
+    pub fn add_move(&mut self, turn: Turn, colour: Colour, board: BoardState) {
+        let parent = self.pointer;
+        let existing = self.nodes[parent].children.iter().position(|&child| {
+            self.nodes[child].turn == turn && self.nodes[child].colour == colour
+        });
+
+        let position = match existing {
+            Some(position) => position,
+            None => {
+                let id = self.nodes.len();
+                self.nodes.push(GameNode {
+                    turn,
+                    colour,
+                    board,
+                    parent: Some(parent),
+                    children: Vec::new(),
+                    selected: 0,
+                });
+                self.nodes[parent].children.push(id);
+                self.nodes[parent].children.len() - 1
+            }
+        };
+
+        self.nodes[parent].selected = position;
+        self.pointer = self.nodes[parent].children[position];
+    }
+
+    /// removes the current node (and every variation after it) and moves back to its parent
+    /// 
+    /// the parent keeps following the variation before the removed one, and the ids of the nodes after it shift down
+    pub fn undo(&mut self) -> Result<(), TreeErrors> {
+        let Some(parent) = self.nodes[self.pointer].parent else {
+            return Err(TreeErrors::BelowZero);
+        };
+
+        let removed = self.pointer;
+        let position = self.nodes[parent].children.iter().position(|&child| child == removed).unwrap();
+        let node = &mut self.nodes[parent];
+        node.children.remove(position);
+        if node.selected > position || (node.selected == position && position > 0) {
+            node.selected -= 1;
+        }
+
+        // collect the removed node and everything after it
+        let mut subtree = vec![removed];
+        let mut i = 0;
+        while i < subtree.len() {
+            subtree.extend(self.nodes[subtree[i]].children.iter().copied());
+            i += 1;
+        }
+
+        let mut kept = vec![true; self.nodes.len()];
+        for &id in &subtree {
+            kept[id] = false;
+        }
+
+        let mut new_ids = vec![usize::MAX; self.nodes.len()];
+        let mut next = 0;
+        for (id, &keep) in kept.iter().enumerate() {
+            if keep {
+                new_ids[id] = next;
+                next += 1;
+            }
+        }
+
+        let nodes = std::mem::take(&mut self.nodes);
+        self.nodes = nodes.into_iter().zip(kept).filter(|(_, keep)| *keep).map(|(mut node, _)| {
+            node.parent = node.parent.map(|parent| new_ids[parent]);
+            node.children = node.children.iter().map(|&child| new_ids[child]).collect();
+            node
+        }).collect();
+
+        self.pointer = new_ids[parent];
+        Ok(())
+    }
+
+    /// returns the move number of the current node
     pub fn get_pointer(&self) -> usize {
-        self.pointer
+        self.get_path().len() - 1
     }
 
+    /// returns the move number at the end of the current line
     pub fn get_length(&self) -> usize {
-        self.board_states.len() - 1
+        let mut length = self.get_pointer();
+        let mut node = &self.nodes[self.pointer];
+
+        while let Some(&child) = node.children.get(node.selected) {
+            node = &self.nodes[child];
+            length += 1;
+        }
+        length
     }
 
-    /// returns true if the game is over by resignation or agreement
-    pub fn check_end(&self) -> bool {
-        if self.board_states.len() < 3 {
-            return false;
+    /// returns the ids of the nodes from the root to the current node
+    pub fn get_path(&self) -> Vec<usize> {
+        let mut path = vec![self.pointer];
+
+        while let Some(parent) = self.nodes[*path.last().unwrap()].parent {
+            path.push(parent);
         }
-    
-        match self.board_states.last().unwrap() {
-            (Turn::Resign, _) => true,
-            _ => {
-                if let Some((first_last, _)) = self.board_states.get(self.board_states.len() - 2) {
-                    if let Some((first_second_last, _)) = self.board_states.last() {
-                        match (first_last, first_second_last) {
-                            (Turn::Pass, Turn::Pass) => true,
-                            _ => false,
-                        }
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
+
+        path.reverse();
+        path
+    }
+
+    /// returns (the current variation, the number of variations) for the current node
+    pub fn get_variation(&self) -> (usize, usize) {
+        match self.nodes[self.pointer].parent {
+            Some(parent) => {
+                let siblings = &self.nodes[parent].children;
+                (siblings.iter().position(|&id| id == self.pointer).unwrap() + 1, siblings.len())
             }
+            None => (1, 1),
+        }
+    }
+
+    /// returns true if the game is over by resignation or agreement
+    pub fn check_end(&self) -> bool {
+        let node = &self.nodes[self.pointer];
+
+        match node.turn {
+            Turn::Resign => true,
+            Turn::Pass => match node.parent {
+                Some(parent) => self.nodes[parent].parent.is_some() && matches!(self.nodes[parent].turn, Turn::Pass),
+                None => false,
+            },
+            _ => false,
         }
     }
 
-    /// returns true if the pointer is on the current position
-    pub fn up_to_date(&self) -> bool {
-        self.pointer == self.board_states.len() - 1
+    /// returns true if the pointer is at the end of the current line
+    pub fn _up_to_date(&self) -> bool {
+        self.nodes[self.pointer].children.is_empty()
     }
 
     pub fn get_board(&self) -> (Colour, BoardState) {
-        let (_, board) = &self.board_states[self.pointer];
+        let node = &self.nodes[self.pointer];
 
-        let colour;
+        let colour = match node.colour { // the colour to play is whoever didn't play the current turn
+            Colour::Empty => self.first_colour,
+            colour => colour.swap_turn(),
+        };
 
-        if self.pointer % 2 == 0 {
-            colour = Colour::Black;
-        } else {
-            colour = Colour::White;
-        }
+        (colour, node.board.clone())
+    }
+
+    /// returns the colour to play from the root
+    pub fn get_first_colour(&self) -> Colour {
+        self.first_colour
+    }
+
+    /// sets the colour to play from the root
+    pub fn set_first_colour(&mut self, colour: Colour) {
+        self.first_colour = colour;
+    }
 
-        (colour, board.clone())
+    /// replaces the starting position (ie to add handicap stones), which should only be done before any turns are played
+    pub fn set_root_board(&mut self, board: BoardState) {
+        self.nodes[0].board = board;
     }
 
     /// Returns the last move if it is not a pass or resignation
     pub fn get_last_move(&self) -> Option<Coordinate> {
-        let data = self.board_states[self.pointer].0;
-
-        match data {
+        match self.nodes[self.pointer].turn {
             Turn::Move(coordinate) => Some(coordinate),
             _ => None
         }
     }
-}
\ No newline at end of file
+
+    /// returns the root of the tree
+    pub fn get_root(&self) -> &GameNode {
+        &self.nodes[0]
+    }
+
+    /// returns the node with the given id
+    pub fn get_node(&self, id: usize) -> &GameNode {
+        &self.nodes[id]
+    }
+
+    /// returns the node currently being looked at
+    pub fn get_current_node(&self) -> &GameNode {
+        &self.nodes[self.pointer]
+    }
+
+    /// returns the id of the current node
+    pub fn get_current_id(&self) -> usize {
+        self.pointer
+    }
+
+    /// returns every turn played from the root to the current node along with the colour that played it
+    pub fn get_turns(&self) -> Vec<(Turn, Colour)> {
+        self.get_path().iter().skip(1).map(|&id| (self.nodes[id].turn, self.nodes[id].colour)).collect()
+    }
+
+    /// returns how many passes in a row were played up to the current node
+    pub fn get_consecutive_passes(&self) -> usize {
+        self.get_turns().iter().rev().take_while(|(turn, _)| *turn == Turn::Pass).count()
+    }
+
+    /// returns the colour that played the current turn (Empty for the root)
+    pub fn get_last_colour(&self) -> Colour {
+        self.nodes[self.pointer].colour
+    }
+
+    /// changes the rules of every position in the tree
+    pub fn set_rules(&mut self, rules: Rules) {
+        for node in &mut self.nodes {
+            node.board.rules = rules;
+        }
+    }
+
+    /// returns the board of the current node
+    pub fn get_current_board(&self) -> &BoardState {
+        &self.nodes[self.pointer].board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// plays the points in order from the current node, alternating colours from the colour to play
+    fn play(tree: &mut GameTree, points: &[usize]) {
+        for &point in points {
+            let (colour, board) = tree.get_board();
+            let coordinate = Coordinate::Index(point, board.size);
+            let board = board.add_stone(coordinate, colour).unwrap();
+            tree.add_move(Turn::Move(coordinate), colour, board);
+        }
+    }
+
+    fn last_point(tree: &GameTree) -> Option<usize> {
+        tree.get_last_move().map(|coordinate| coordinate.get_index())
+    }
+
+    /// checks every node is the child of its parent and the parent of its children
+    fn assert_linked(tree: &GameTree) {
+        for (id, node) in tree.nodes.iter().enumerate() {
+            if let Some(parent) = node.parent {
+                assert!(tree.nodes[parent].children.contains(&id));
+            }
+            for &child in &node.children {
+                assert_eq!(tree.nodes[child].parent, Some(id));
+            }
+            assert!(node.children.is_empty() || node.selected < node.children.len());
+        }
+    }
+
+    #[test]
+    fn moves_from_an_earlier_position_start_a_variation() {
+        let mut tree = GameTree::new(5).unwrap();
+        play(&mut tree, &[0, 1, 2]);
+        tree.move_back().ok();
+        tree.move_back().ok();
+        play(&mut tree, &[3, 4]);
+
+        assert_eq!(tree.get_pointer(), 3);
+        assert_eq!(tree.get_turns().len(), 3);
+        tree.move_back().ok();
+        assert_eq!(tree.get_variation(), (2, 2));
+
+        tree.change_variation(false).ok();
+        assert_eq!(last_point(&tree), Some(1));
+        assert_eq!(tree.get_length(), 3);
+        assert!(tree.change_variation(false).is_err());
+        assert_linked(&tree);
+    }
+
+    #[test]
+    fn playing_an_existing_move_follows_its_variation() {
+        let mut tree = GameTree::new(5).unwrap();
+        play(&mut tree, &[0, 1]);
+        tree.move_back().ok();
+        play(&mut tree, &[3]);
+        tree.move_back().ok();
+        play(&mut tree, &[1]);
+
+        assert_eq!(tree.nodes.len(), 4);
+        assert_eq!(tree.get_variation(), (1, 2));
+    }
+
+    #[test]
+    fn the_main_line_is_the_first_variation() {
+        let mut tree = GameTree::new(5).unwrap();
+        play(&mut tree, &[0, 1, 2]);
+        tree.move_back().ok();
+        play(&mut tree, &[3, 4]);
+
+        tree.select_main_line();
+        assert_eq!(tree.get_pointer(), 3);
+        assert_eq!(last_point(&tree), Some(2));
+    }
+
+    #[test]
+    fn undo_in_a_variation_returns_to_the_previous_one() {
+        let mut tree = GameTree::new(5).unwrap();
+        play(&mut tree, &[0, 1, 2]);
+        tree.move_back().ok();
+        play(&mut tree, &[3, 4]);
+        tree.move_back().ok();
+        play(&mut tree, &[5]);
+        assert_eq!(tree.get_variation(), (2, 2));
+
+        tree.undo().ok();
+        assert_eq!(last_point(&tree), Some(3));
+        tree.move_forward().ok();
+        assert_eq!(last_point(&tree), Some(4));
+
+        tree.move_back().ok();
+        tree.undo().ok();
+        assert_eq!(last_point(&tree), Some(1));
+        tree.reset();
+        assert_eq!(last_point(&tree), Some(2));
+        assert_linked(&tree);
+    }
+
+    #[test]
+    fn undo_removes_the_nodes_after_it() {
+        let mut tree = GameTree::new(5).unwrap();
+        play(&mut tree, &[0, 1, 2]);
+        tree.move_back().ok();
+        play(&mut tree, &[3, 4]);
+        tree.move_back().ok();
+        tree.move_back().ok();
+
+        tree.undo().ok();
+        assert_eq!(tree.nodes.len(), 2);
+        assert_eq!(last_point(&tree), Some(0));
+        assert_linked(&tree);
+
+        tree.undo().ok();
+        assert_eq!(tree.nodes.len(), 1);
+        assert_eq!(tree.get_current_id(), 0);
+        assert!(tree.undo().is_err());
+        assert_linked(&tree);
+
+        play(&mut tree, &[6]);
+        assert_eq!(tree.get_current_id(), 1);
+        assert_linked(&tree);
+    }
+}
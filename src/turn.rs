@@ -1,6 +1,6 @@
 use crate::coordinate::Coordinate;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Turn {
     Move(Coordinate), // move a stone (coordinate could either be the position or index)
     Pass, // 2 passes and the game is over
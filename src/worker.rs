@@ -0,0 +1,124 @@
+// runs a player's search on a background thread so the gui doesn't freeze while it thinks
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use go_rs::colour::Colour;
+use go_rs::game_state::{GameState, MoveAnalysis};
+use go_rs::player::Player;
+use go_rs::turn::Turn;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100); // how often progress is sent back
+
+#[derive(Debug)]
+pub enum SearchErrors { // errors regarding searches running in the background
+    Panicked, // the search thread stopped without handing the player back
+}
+
+impl std::fmt::Display for SearchErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchErrors::Panicked => write!(f, "the search stopped without choosing a turn"),
+        }
+    }
+}
+
+/// the turn a search chose, with the player handed back
+pub type Finished = (Turn, Box<dyn Player>);
+
+/// the messages the worker sends back to the gui
+enum SearchMessage {
+    Progress(usize, Duration, Vec<MoveAnalysis>), // iterations, time and analysis so far
+    Finished(Turn, Box<dyn Player>), // the player is handed back along with its turn
+}
+
+/// a search running on another thread
+pub struct SearchWorker {
+    receiver: Receiver<SearchMessage>,
+    stop: Arc<AtomicBool>,
+    pub colour: Colour, // the colour being searched for
+    pub iterations: usize,
+    pub elapsed: Duration,
+    pub analysis: Vec<MoveAnalysis>, // the moves the search is considering, most visited first
+}
+
+impl SearchWorker {
+    /// starts the player choosing a turn for the colour to play in the current position of the game
+    pub fn start(mut player: Box<dyn Player>, game: &GameState) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let game = game.clone();
+        let colour = game.turn;
+        let thread_stop = stop.clone();
+
+        thread::spawn(move || {
+            let mut last_update = Duration::ZERO;
+            let progress_sender = sender.clone();
+
+            let turn = player.genmove(&game, &thread_stop, &mut |iterations, elapsed, analysis| {
+                if elapsed - last_update >= PROGRESS_INTERVAL {
+                    last_update = elapsed;
+                    let _ = progress_sender.send(SearchMessage::Progress(iterations, elapsed, analysis()));
+                }
+            });
+
+            let _ = sender.send(SearchMessage::Finished(turn, player)); // the gui may have stopped listening
+        });
+
+        SearchWorker {
+            receiver,
+            stop,
+            colour,
+            iterations: 0,
+            elapsed: Duration::ZERO,
+            analysis: Vec::new(),
+        }
+    }
+
+    /// Reads any messages from the worker, returning the turn and the player once it has finished
+    /// 
+    /// the search thread panicking loses the player, and no turn is chosen for it
+    pub fn poll(&mut self) -> Option<Result<Finished, SearchErrors>> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(SearchMessage::Progress(iterations, elapsed, analysis)) => {
+                    self.iterations = iterations;
+                    self.elapsed = elapsed;
+                    self.analysis = analysis;
+                }
+                Ok(SearchMessage::Finished(turn, player)) => return Some(Ok((turn, player))),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => return Some(Err(SearchErrors::Panicked)),
+            }
+        }
+    }
+
+    /// Stops the search and waits for the player to be handed back, discarding its turn
+    /// 
+    /// players that can't be stopped early (ie gtp engines) are waited for until they have chosen a turn
+    pub fn cancel(self) -> Option<Box<dyn Player>> {
+        self.stop.store(true, Ordering::Relaxed);
+        loop {
+            match self.receiver.recv() {
+                Ok(SearchMessage::Finished(_, player)) => return Some(player),
+                Ok(SearchMessage::Progress(..)) => continue,
+                Err(_) => return None,
+            }
+        }
+    }
+
+    /// stops the search early, which still finishes with the best turn found so far
+    pub fn force(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for SearchWorker {
+    /// dropping the worker cancels the search
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
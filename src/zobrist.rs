@@ -1,52 +1,224 @@
-use rand::Rng;
-use std::collections::{HashMap, HashSet};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::{Arc, OnceLock};
 
+use crate::board_state::MAX_BOARD_SIZE;
 use crate::colour::Colour;
+use crate::rules::KoRule;
 
+const KEY_SEED: u64 = 0x0067_6f2d_7273; // the keys are the same in every run so hashes can be stored and compared
+
+/// the random values xored together to hash a position, shared by every board
+struct ZobristKeys {
+    stones: Vec<[u64; 2]>, // for a black and a white stone on each point
+    ko: Vec<u64>, // for each point that can't be played because of a ko
+    sizes: Vec<u64>, // so the same stones on different boards don't match
+    white_to_move: u64,
+}
+
+/// returns the keys, generating them the first time they are needed
+fn keys() -> &'static ZobristKeys {
+    static KEYS: OnceLock<ZobristKeys> = OnceLock::new();
+
+    KEYS.get_or_init(|| {
+        let mut rng = StdRng::seed_from_u64(KEY_SEED);
+        let points = MAX_BOARD_SIZE * MAX_BOARD_SIZE;
+
+        ZobristKeys {
+            stones: (0..points).map(|_| [rng.gen(), rng.gen()]).collect(),
+            ko: (0..points).map(|_| rng.gen()).collect(),
+            sizes: (0..=MAX_BOARD_SIZE).map(|_| rng.gen()).collect(),
+            white_to_move: rng.gen(),
+        }
+    })
+}
+
+/// returns the key for a stone of the colour on the point with the given index
+pub fn stone_key(index: usize, colour: Colour) -> u64 {
+    keys().stones[index][colour.into_usize() - 1]
+}
+
+/// a position in the history of a game, linked to the one before it so boards on the same line share their history
+#[derive(Debug)]
+struct HistoryEntry {
+    hash: u64, // the hash of the stones
+    colour: Colour, // the colour that played into the position
+    previous: Option<Arc<HistoryEntry>>,
+}
+
+impl Drop for HistoryEntry {
+    /// drops a long history one entry at a time instead of recursively
+    fn drop(&mut self) {
+        let mut previous = self.previous.take();
+        while let Some(entry) = previous {
+            match Arc::try_unwrap(entry) {
+                Ok(mut entry) => previous = entry.previous.take(),
+                Err(_) => break, // another board still shares the rest
+            }
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct ZobristTable {
-    map: HashMap<(usize, Colour), u64>,
-    visited: HashSet<u64>,
+    history: Option<Arc<HistoryEntry>>, // every position on the way to this one, most recent first
+    current: u64, // the hash of the stones in the current position
+    previous: Option<u64>, // the hash of the stones before the last turn (for simple ko)
+    to_move: Colour,
+    ko_point: Option<usize>, // the index of the point that can't be played straight away because of a ko
 }
 
 impl ZobristTable {
     pub fn new(size: usize) -> Self {
-        let mut rng = rand::thread_rng();
-        let mut table = HashMap::new();
+        let mut table = ZobristTable {
+            history: None,
+            current: keys().sizes[size],
+            previous: None,
+            to_move: Colour::Black,
+            ko_point: None,
+        };
+        table.push_history(table.current, Colour::Empty); // the empty board, which nobody played into
+        table
+    }
 
-        for i in 0..(size * size) {
-            for &colour in &[Colour::Black, Colour::White] {
-                let random_value: u64 = rng.gen();
-                table.insert((i, colour), random_value);
-            }
+    /// creates the table for stones that were set up rather than played, whose history starts with them
+    pub fn from_stones(size: usize, stones: &[Colour], to_move: Colour) -> Self {
+        let current = stones.iter().enumerate()
+            .filter(|(_, &colour)| colour != Colour::Empty)
+            .fold(keys().sizes[size], |hash, (index, &colour)| hash ^ stone_key(index, colour));
+
+        let mut table = ZobristTable {
+            history: None,
+            current,
+            previous: None,
+            to_move,
+            ko_point: None,
+        };
+        table.push_history(current, Colour::Empty); // nobody played into the position
+        table
+    }
+
+    /// returns the colour to play next
+    pub fn get_to_move(&self) -> Colour {
+        self.to_move
+    }
+
+    /// returns the point that can't be played straight away because of a ko
+    pub fn get_ko_point(&self) -> Option<usize> {
+        self.ko_point
+    }
+
+    /// returns the hash of the stones on the board, which is updated with stone_key as they are played and captured
+    pub fn get_position(&self) -> u64 {
+        self.current
+    }
+
+    /// returns the hash of the whole position: the stones, the colour to play and any ko point
+    pub fn get_hash(&self) -> u64 {
+        let keys = keys();
+        let mut hash = self.current;
+
+        if self.to_move == Colour::White {
+            hash ^= keys.white_to_move;
         }
+        if let Some(point) = self.ko_point {
+            hash ^= keys.ko[point];
+        }
+        hash
+    }
 
-        ZobristTable { map: table, visited: HashSet::new()  }
+    /// adds a position to the front of the history, which is shared with the board it came from
+    fn push_history(&mut self, hash: u64, colour: Colour) {
+        let previous = self.history.take();
+        self.history = Some(Arc::new(HistoryEntry { hash, colour, previous }));
     }
 
-    /// Compute the Zobrist hash of a given board
-    fn zobrist_hash(&self, board: &[Colour]) -> u64 { 
-        let mut hash_value: u64 = 0;
-    
-        for (i, &colour) in board.iter().enumerate() {
-            if colour != Colour::Empty {
-                hash_value ^= self.map.get(&(i, colour)).unwrap();
-            }
+    /// returns every position in the history with the colour that played into it, most recent first
+    fn get_history(&self) -> impl Iterator<Item = (u64, Colour)> + '_ {
+        std::iter::successors(self.history.as_deref(), |entry| entry.previous.as_deref()).map(|entry| (entry.hash, entry.colour))
+    }
+
+    /// Inserts the hash of the stones after the colour played into it, along with any ko point it left
+    pub fn insert_position(&mut self, hash: u64, colour: Colour, ko_point: Option<usize>) {
+        self.push_history(hash, colour);
+        self.previous = Some(self.current);
+        self.current = hash;
+        self.to_move = colour.swap_turn();
+        self.ko_point = ko_point;
+    }
+
+    /// records a pass by the given colour, which leaves the position the same but lets a ko be retaken
+    pub fn insert_pass(&mut self, colour: Colour) {
+        self.push_history(self.current, colour);
+        self.previous = Some(self.current);
+        self.to_move = colour.swap_turn();
+        self.ko_point = None;
+    }
+
+    /// returns true if both tables have the same positions in their history, played into by the same colours
+    pub fn same_history(&self, other: &ZobristTable) -> bool {
+        self.get_history().eq(other.get_history())
+    }
+
+    /// Check if playing into the stones with the given hash with the given colour would break the ko rule
+    /// 
+    /// superko only looks back along the line of play that led to this board
+    pub fn contains_position(&self, hash: u64, colour: Colour, ko: KoRule) -> bool {
+        match ko {
+            KoRule::Simple => self.previous == Some(hash),
+            KoRule::PositionalSuperko => self.get_history().any(|(position, _)| position == hash),
+            KoRule::SituationalSuperko => self.get_history().any(|position| position == (hash, colour)),
         }
-    
-        hash_value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_the_same_every_time() {
+        assert_eq!(ZobristTable::new(9).get_hash(), ZobristTable::new(9).get_hash());
+        assert_ne!(ZobristTable::new(9).get_hash(), ZobristTable::new(13).get_hash());
     }
 
-    /// Get the zobrist hash and insert it
-    pub fn insert_position(&mut self, board: &[Colour]) { 
-        let hash_value = self.zobrist_hash(board);
-        self.visited.insert(hash_value);
+    #[test]
+    fn simple_ko_only_looks_at_the_position_before_the_last_turn() {
+        let mut table = ZobristTable::new(5);
+        let empty = table.get_position();
+        table.insert_position(empty ^ stone_key(0, Colour::Black), Colour::Black, None);
+
+        assert!(table.contains_position(empty, Colour::White, KoRule::Simple));
+        table.insert_position(table.get_position() ^ stone_key(1, Colour::White), Colour::White, None);
+        assert!(!table.contains_position(empty, Colour::Black, KoRule::Simple));
+    }
+
+    #[test]
+    fn situational_superko_also_needs_the_same_player() {
+        // black plays into a position, which white then recreates
+        let mut table = ZobristTable::new(5);
+        let position = table.get_position() ^ stone_key(0, Colour::Black);
+        table.insert_position(position, Colour::Black, None);
+        table.insert_position(position ^ stone_key(1, Colour::White), Colour::White, None);
+
+        assert!(table.contains_position(position, Colour::White, KoRule::PositionalSuperko));
+        assert!(!table.contains_position(position, Colour::White, KoRule::SituationalSuperko));
+        assert!(table.contains_position(position, Colour::Black, KoRule::SituationalSuperko));
     }
 
-    /// Check if the board's zobrist hash exists
-    pub fn contains_position(&self, board: &[Colour]) -> bool { 
-        let hash_value = self.zobrist_hash(board);
-        self.visited.contains(&hash_value)
+    #[test]
+    fn side_to_move_and_ko_change_the_hash() {
+        let mut table = ZobristTable::new(5);
+        let position = table.get_position() ^ stone_key(0, Colour::Black);
+        table.insert_position(position, Colour::Black, None);
+        let white_to_move = table.get_hash();
+
+        let mut passed = table.clone();
+        passed.insert_pass(Colour::White);
+        assert_ne!(passed.get_hash(), white_to_move);
+
+        let mut with_ko = ZobristTable::new(5);
+        with_ko.insert_position(position, Colour::Black, Some(1));
+        assert_ne!(with_ko.get_hash(), white_to_move);
     }
 }